@@ -21,7 +21,7 @@ fn p2_fuel(m: i32) -> i32 {
 fn p2() {
     let rdr = BufReader::new(File::open("input").unwrap());
     let mass_iter = rdr.lines().map(|l| l.unwrap().parse::<i32>().unwrap());
-    let fuel_sum: i32 = mass_iter.map(|m| p2_fuel(m)).sum();
+    let fuel_sum: i32 = mass_iter.map(p2_fuel).sum();
 
     println!("{:}", fuel_sum);
 }
@@ -1,68 +1,13 @@
-use std::convert::TryFrom;
+use intcode::symbolic::{Executor, Goal};
+use intcode::{read_mem, Cpu, Status};
 
-#[derive(Debug)]
-enum Intcode {
-    Add,
-    Mult,
-    Halt,
-}
-
-#[derive(Debug)]
-enum ErrorIntcode {
-    InvalidOpcode,
-}
-
-impl TryFrom<i32> for Intcode {
-    type Error = ErrorIntcode;
-
-    fn try_from(value: i32) -> Result<Self, Self::Error> {
-        match value {
-            1 => Ok(Intcode::Add),
-            2 => Ok(Intcode::Mult),
-            99 => Ok(Intcode::Halt),
-            _ => Err(ErrorIntcode::InvalidOpcode),
-        }
-    }
-}
-
-fn read_mem() -> Vec<i32> {
-    let text = std::fs::read_to_string("input").unwrap();
-    let text = text.trim();
-    text.split(',').map(|s| s.parse().unwrap()).collect()
-}
-
-fn run(noun: i32, verb: i32) -> i32 {
+fn run(noun: i64, verb: i64) -> i64 {
     let mut mem = read_mem();
-    let mut pc = 0;
     mem[1] = noun;
     mem[2] = verb;
-    loop {
-        let op: Intcode = Intcode::try_from(mem[pc]).unwrap();
-        match op {
-            Intcode::Add => {
-                let (p_a, p_b, p_c) = (
-                    mem[pc + 1] as usize,
-                    mem[pc + 2] as usize,
-                    mem[pc + 3] as usize,
-                );
-                mem[p_c] = mem[p_a] + mem[p_b];
-                pc += 4;
-            }
-            Intcode::Mult => {
-                let (p_a, p_b, p_c) = (
-                    mem[pc + 1] as usize,
-                    mem[pc + 2] as usize,
-                    mem[pc + 3] as usize,
-                );
-                mem[p_c] = mem[p_a] * mem[p_b];
-                pc += 4;
-            }
-            Intcode::Halt => {
-                break;
-            }
-        }
-    }
-    mem[0]
+    let mut cpu = Cpu::with_mem(mem);
+    assert_eq!(cpu.run(&mut None), Status::Halted);
+    cpu.mem[0]
 }
 
 fn p1() {
@@ -70,20 +15,13 @@ fn p1() {
 }
 
 fn p2() {
-    let tgt = 19690720;
-    let mut noun = 0;
-    let mut verb = 0;
+    let mut sym = Executor::new(&read_mem());
+    let noun = sym.symbol_at(1, 0..=99);
+    let verb = sym.symbol_at(2, 0..=99);
 
-    loop {
-        if run(noun, verb) == tgt {
-            println!("Part 2: {}", 100 * noun + verb);
-            break;
-        } else if run(noun + 1, verb) < tgt {
-            noun += 1;
-        } else {
-            verb += 1;
-        }
-    }
+    let found = sym.solve(Goal::Mem(0), 19690720).unwrap();
+    let v = found.expect("No noun and verb produce the target");
+    println!("Part 2: {}", 100 * v[noun] + v[verb]);
 }
 
 fn main() {
@@ -35,6 +35,7 @@ impl Dir {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
 enum ParseDirError {
     Int(ParseIntError),
@@ -95,7 +96,7 @@ fn main() {
     let isect: Vec<_> = wire_maps[0]
         .keys()
         .filter(|p| wire_maps[1].contains_key(p))
-        .map(|p| *p)
+        .copied()
         .collect();
 
     let minp = *isect.iter().min_by_key(|&&p| m_dist_o(p)).unwrap();
@@ -62,7 +62,7 @@ fn is_blocked(p: Pos, o: Pos, starmap: &StarMap) -> bool {
 fn get_los(p: Pos, starmap: &StarMap) -> usize {
     starmap
         .iter()
-        .filter(|(&k, &v)| k != p && v == Spot::Asteroid && !is_blocked(p, k, &starmap))
+        .filter(|(&k, &v)| k != p && v == Spot::Asteroid && !is_blocked(p, k, starmap))
         .count()
 }
 
@@ -73,7 +73,7 @@ fn calc_slope(p: Pos) -> f32 {
 fn ordered_hitlist(p: Pos, starmap: &StarMap) -> Vec<Pos> {
     let mut all: Vec<_> = starmap
         .iter()
-        .filter(|(&k, &v)| k != p && v == Spot::Asteroid && !is_blocked(p, k, &starmap))
+        .filter(|(&k, &v)| k != p && v == Spot::Asteroid && !is_blocked(p, k, starmap))
         .map(|a| *a.0)
         .collect();
 
@@ -12,6 +12,7 @@ struct Moon {
     v: Pos,
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
 enum ParseError {
     Int(ParseIntError),
@@ -8,6 +8,7 @@ struct Reaction {
     output: (String, usize),
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
 enum ParseError {
     Int(ParseIntError),
@@ -61,16 +62,16 @@ fn produce(
     let mut produced = 0;
 
     if let Some(&o) = r.input.get("ORE") {
-        let times = (amount + r.output.1 - 1) / r.output.1;
+        let times = amount.div_ceil(r.output.1);
         ore_cnt += o * times;
         produced += r.output.1 * times;
     } else {
-        let times = (amount + r.output.1 - 1) / r.output.1;
+        let times = amount.div_ceil(r.output.1);
         for (n, &c) in &r.input {
             let need = c * times;
             let have = *resources.get(n).unwrap_or(&0);
             if have < need {
-                ore_cnt += produce(&n, need - have, rules, resources);
+                ore_cnt += produce(n, need - have, rules, resources);
             }
 
             // consume the available resources once they're ready
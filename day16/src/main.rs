@@ -1,4 +1,4 @@
-use std::iter::repeat;
+use std::iter::repeat_n;
 
 fn main() {
     let base = [0, 1, 0, -1];
@@ -13,8 +13,8 @@ fn main() {
     for _ in 0..100 {
         let mut ph = vec![];
         for p in 1..=cur.len() {
-            let iter = base.iter().flat_map(|e| repeat(e).take(p)).cycle().skip(1);
-            let x = cur.iter().zip(iter).map(|(a, b)| (a * b)).sum::<i16>() % 10;
+            let iter = base.iter().flat_map(|e| repeat_n(e, p)).cycle().skip(1);
+            let x = cur.iter().zip(iter).map(|(a, b)| a * b).sum::<i16>() % 10;
             ph.push(x.abs());
         }
         cur = ph;
@@ -1,215 +1,28 @@
 use std::collections::{HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+use intcode::ascii::{Ascii, ErrorAscii};
+use intcode::Cpu;
 
 type Pos = (i64, i64);
 type TileMap = HashMap<Pos, Tile>;
 
-#[derive(Debug)]
-enum Mode {
-    Pos,
-    Im,
-    Rel,
-}
-
-#[derive(Debug)]
-enum Intcode {
-    Add(Mode, Mode, Mode),
-    Mult(Mode, Mode, Mode),
-    In(Mode),
-    Out(Mode),
-    Jit(Mode, Mode),
-    Jif(Mode, Mode),
-    Lt(Mode, Mode, Mode),
-    Equ(Mode, Mode, Mode),
-    Adj(Mode),
-    Halt,
-}
-
-#[derive(Debug)]
-enum ErrorIntcode {
-    InvalidOpcode,
-    InvalidMode,
-}
-
-impl TryFrom<i64> for Mode {
-    type Error = ErrorIntcode;
-
-    fn try_from(value: i64) -> Result<Self, Self::Error> {
-        match value {
-            0 => Ok(Mode::Pos),
-            1 => Ok(Mode::Im),
-            2 => Ok(Mode::Rel),
-            _ => Err(ErrorIntcode::InvalidMode),
-        }
-    }
-}
-
-impl TryFrom<i64> for Intcode {
-    type Error = ErrorIntcode;
-
-    fn try_from(value: i64) -> Result<Self, Self::Error> {
-        let m1: Mode = ((value / 100) % 10).try_into()?;
-        let m2: Mode = ((value / 1000) % 10).try_into()?;
-        let m3: Mode = ((value / 10000) % 10).try_into()?;
-
-        match value % 100 {
-            1 => Ok(Intcode::Add(m1, m2, m3)),
-            2 => Ok(Intcode::Mult(m1, m2, m3)),
-            3 => Ok(Intcode::In(m1)),
-            4 => Ok(Intcode::Out(m1)),
-            5 => Ok(Intcode::Jit(m1, m2)),
-            6 => Ok(Intcode::Jif(m1, m2)),
-            7 => Ok(Intcode::Lt(m1, m2, m3)),
-            8 => Ok(Intcode::Equ(m1, m2, m3)),
-            9 => Ok(Intcode::Adj(m1)),
-            99 => Ok(Intcode::Halt),
-            _ => Err(ErrorIntcode::InvalidOpcode),
-        }
-    }
-}
-
-fn read_mem() -> Vec<i64> {
-    let text = std::fs::read_to_string("input").unwrap();
-    let text = text.trim();
-    text.split(',').map(|s| s.parse().unwrap()).collect()
-}
-
-fn mem_get(mem: &[i64], rel: usize, addr: usize, m: Mode) -> i64 {
-    match m {
-        Mode::Pos => mem[mem[addr] as usize],
-        Mode::Im => mem[addr],
-        Mode::Rel => mem[(mem[addr] + rel as i64) as usize],
-    }
-}
-
-fn mem_set(mem: &mut [i64], rel: usize, addr: usize, m: Mode, v: i64) {
-    match m {
-        Mode::Pos => mem[mem[addr] as usize] = v,
-        Mode::Im => mem[addr] = v,
-        Mode::Rel => mem[(mem[addr] + rel as i64) as usize] = v,
-    }
-}
-
-#[derive(Debug, Clone)]
-struct Cpu {
-    mem: Vec<i64>,
-    pc: usize,
-    rel: usize,
-}
-
-impl Cpu {
-    fn new() -> Cpu {
-        let mut mem = read_mem();
-        // Ugly way to add more memory but whatevz
-        mem.extend(std::iter::repeat(0).take(10000));
-        Cpu { mem, pc: 0, rel: 0 }
-    }
-
-    fn run(&mut self, input: &mut Option<i64>) -> Option<i64> {
-        loop {
-            let op: Intcode = self.mem[self.pc].try_into().unwrap();
-            match op {
-                Intcode::Add(m1, m2, m3) => {
-                    let (p_a, p_b) = (
-                        mem_get(&self.mem, self.rel, self.pc + 1, m1),
-                        mem_get(&self.mem, self.rel, self.pc + 2, m2),
-                    );
-                    mem_set(&mut self.mem, self.rel, self.pc + 3, m3, p_a + p_b);
-                    self.pc += 4;
-                }
-                Intcode::Mult(m1, m2, m3) => {
-                    let (p_a, p_b) = (
-                        mem_get(&self.mem, self.rel, self.pc + 1, m1),
-                        mem_get(&self.mem, self.rel, self.pc + 2, m2),
-                    );
-                    mem_set(&mut self.mem, self.rel, self.pc + 3, m3, p_a * p_b);
-                    self.pc += 4;
-                }
-                Intcode::In(m1) => {
-                    if input.is_none() {
-                        return None;
-                    }
-                    mem_set(
-                        &mut self.mem,
-                        self.rel,
-                        self.pc + 1,
-                        m1,
-                        input.take().expect("No input"),
-                    );
-                    self.pc += 2;
-                }
-                Intcode::Out(m1) => {
-                    let p_a = mem_get(&self.mem, self.rel, self.pc + 1, m1);
-                    //println!("{}", p_a);
-                    self.pc += 2;
-                    return Some(p_a);
-                }
-                Intcode::Jit(m1, m2) => {
-                    let (p_a, p_b) = (
-                        mem_get(&self.mem, self.rel, self.pc + 1, m1),
-                        mem_get(&self.mem, self.rel, self.pc + 2, m2),
-                    );
-                    if p_a != 0 {
-                        self.pc = p_b as usize;
-                    } else {
-                        self.pc += 3;
-                    }
-                }
-                Intcode::Jif(m1, m2) => {
-                    let (p_a, p_b) = (
-                        mem_get(&self.mem, self.rel, self.pc + 1, m1),
-                        mem_get(&self.mem, self.rel, self.pc + 2, m2),
-                    );
-                    if p_a == 0 {
-                        self.pc = p_b as usize;
-                    } else {
-                        self.pc += 3;
-                    }
-                }
-                Intcode::Lt(m1, m2, m3) => {
-                    let (p_a, p_b) = (
-                        mem_get(&self.mem, self.rel, self.pc + 1, m1),
-                        mem_get(&self.mem, self.rel, self.pc + 2, m2),
-                    );
-                    mem_set(&mut self.mem, self.rel, self.pc + 3, m3, (p_a < p_b) as i64);
-                    self.pc += 4;
-                }
-                Intcode::Equ(m1, m2, m3) => {
-                    let (p_a, p_b) = (
-                        mem_get(&self.mem, self.rel, self.pc + 1, m1),
-                        mem_get(&self.mem, self.rel, self.pc + 2, m2),
-                    );
-                    mem_set(
-                        &mut self.mem,
-                        self.rel,
-                        self.pc + 3,
-                        m3,
-                        (p_a == p_b) as i64,
-                    );
-                    self.pc += 4;
-                }
-                Intcode::Adj(m1) => {
-                    let p_a = mem_get(&self.mem, self.rel, self.pc + 1, m1);
-                    self.rel = (self.rel as i64 + p_a) as usize;
-                    self.pc += 2;
-                }
-                Intcode::Halt => {
-                    break;
-                }
-            }
-        }
-        None
-    }
-}
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Tile {
     Open,
     Scaffold,
-    Robot,
+    Robot(Dir),
+    // Fell off the scaffold
+    Tumbling,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Hash, Debug, Clone, Copy, PartialEq, Eq)]
 enum Dir {
     N,
     S,
@@ -230,7 +43,11 @@ impl TryFrom<char> for Tile {
         let out = match c {
             '#' => Tile::Scaffold,
             '.' => Tile::Open,
-            '^' => Tile::Robot,
+            '^' => Tile::Robot(Dir::N),
+            'v' => Tile::Robot(Dir::S),
+            '<' => Tile::Robot(Dir::W),
+            '>' => Tile::Robot(Dir::E),
+            'X' => Tile::Tumbling,
             _ => return Err(()),
         };
 
@@ -238,6 +55,20 @@ impl TryFrom<char> for Tile {
     }
 }
 
+impl Tile {
+    fn to_c(self) -> char {
+        match self {
+            Tile::Open => '.',
+            Tile::Scaffold => '#',
+            Tile::Robot(Dir::N) => '^',
+            Tile::Robot(Dir::S) => 'v',
+            Tile::Robot(Dir::W) => '<',
+            Tile::Robot(Dir::E) => '>',
+            Tile::Tumbling => 'X',
+        }
+    }
+}
+
 impl Dir {
     fn mv(self, p: Pos) -> Pos {
         match self {
@@ -258,55 +89,8 @@ impl Dir {
     }
 }
 
-#[derive(Debug)]
-struct Droid {
-    pos: Pos,
-    cpu: Cpu,
-}
-
-impl Droid {
-    fn new() -> Droid {
-        Droid {
-            pos: (0, 0),
-            cpu: Cpu::new(),
-        }
-    }
-
-    fn get_i64(&mut self) -> Option<i64> {
-        self.cpu.run(&mut None)
-    }
-
-    fn get_char(&mut self) -> Option<char> {
-        self.cpu.run(&mut None).map(|v| (v as u8).into())
-    }
-
-    fn start(&mut self) {
-        self.cpu.mem[0] = 2;
-    }
-
-    fn write_fn(&mut self, fntext: &str) {
-        for c in fntext.chars() {
-            let asc = c as u8;
-            let mut feed = Some(asc as i64);
-
-            while feed.is_some() {
-                if let Some(o) = self.cpu.run(&mut feed) {
-                    print!("{}", o as u8 as char);
-                }
-            }
-        }
-
-        let mut feed = Some('\n' as i64);
-        while feed.is_some() {
-            if let Some(o) = self.cpu.run(&mut feed) {
-                print!("{}", o as u8 as char);
-            }
-        }
-    }
-}
-
-#[allow(dead_code)]
-fn draw_map(tilemap: &TileMap) {
+fn map_text(tilemap: &TileMap) -> String {
+    let mut out = String::new();
     let (max_x, max_y) = (
         tilemap.keys().map(|p| p.0).max().unwrap(),
         tilemap.keys().map(|p| p.1).max().unwrap(),
@@ -321,16 +105,38 @@ fn draw_map(tilemap: &TileMap) {
         for x in min_x..=max_x {
             let t = tilemap.get(&(x, y)).unwrap_or(&Tile::Open);
 
-            let c = match t {
-                Tile::Open => '.',
-                Tile::Scaffold => '#',
-                Tile::Robot => '^',
-            };
+            out.push(t.to_c());
+        }
+        out.push('\n');
+    }
+    out
+}
 
-            print!("{}", c);
+#[allow(dead_code)]
+fn draw_map(tilemap: &TileMap) {
+    print!("{}", map_text(tilemap));
+}
+
+fn parse_map(text: &str) -> TileMap {
+    let mut tilemap = HashMap::new();
+    let mut p = (0, 0);
+    for v in text.chars() {
+        match v {
+            '\n' => p = (0, p.1 + 1),
+            c => {
+                tilemap.insert(p, c.try_into().unwrap());
+                p = (p.0 + 1, p.1);
+            }
         }
-        println!();
     }
+    tilemap
+}
+
+fn robot(tilemap: &TileMap) -> Option<(Pos, Tile)> {
+    tilemap
+        .iter()
+        .find(|(_, &t)| matches!(t, Tile::Robot(_) | Tile::Tumbling))
+        .map(|(&p, &t)| (p, t))
 }
 
 fn get4(p: Pos) -> [Pos; 4] {
@@ -346,26 +152,19 @@ fn get4w(p: Pos) -> [(Dir, Pos); 4] {
     ]
 }
 
-fn turns_to_cmd(turns: &[(Turn, i32)]) -> String {
-    let tx: Vec<String> = turns
-        .iter()
-        .map(|(t, v)| format!("{},{}", t.to_c(), v))
-        .collect();
-    tx.join(",")
-}
-
 impl Turn {
-    fn from_dirs(a: Dir, b: Dir) -> Turn {
+    // `None` when `b` is straight ahead or behind
+    fn from_dirs(a: Dir, b: Dir) -> Option<Turn> {
         match (a, b) {
-            (Dir::N, Dir::E) => Turn::R,
-            (Dir::N, Dir::W) => Turn::L,
-            (Dir::S, Dir::E) => Turn::L,
-            (Dir::S, Dir::W) => Turn::R,
-            (Dir::W, Dir::N) => Turn::R,
-            (Dir::W, Dir::S) => Turn::L,
-            (Dir::E, Dir::N) => Turn::L,
-            (Dir::E, Dir::S) => Turn::R,
-            _ => panic!("Invalid"),
+            (Dir::N, Dir::E) => Some(Turn::R),
+            (Dir::N, Dir::W) => Some(Turn::L),
+            (Dir::S, Dir::E) => Some(Turn::L),
+            (Dir::S, Dir::W) => Some(Turn::R),
+            (Dir::W, Dir::N) => Some(Turn::R),
+            (Dir::W, Dir::S) => Some(Turn::L),
+            (Dir::E, Dir::N) => Some(Turn::L),
+            (Dir::E, Dir::S) => Some(Turn::R),
+            _ => None,
         }
     }
 
@@ -377,135 +176,690 @@ impl Turn {
     }
 }
 
-fn moves_to_turns(moves: &[(Dir, i32)]) -> Vec<(Turn, i32)> {
-    let mut dir = Dir::N;
-    let mut turns = vec![];
+// The robot won't take a routine longer than this, commas included
+const MAX_LINE: usize = 20;
+const MAX_FUNCTIONS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cmd {
+    Turn(Turn),
+    Fwd(i32),
+}
+
+fn cmds_to_line(cmds: &[Cmd]) -> String {
+    let tx: Vec<String> = cmds
+        .iter()
+        .map(|c| match c {
+            Cmd::Turn(t) => t.to_c().to_string(),
+            Cmd::Fwd(v) => v.to_string(),
+        })
+        .collect();
+    tx.join(",")
+}
+
+// Where a routine has got to in the path: the next command, and how much of
+// it is already covered when it's a forward move that got split
+type At = (usize, i32);
+
+// Where the path is after running `f` from `at`, if `f` fits there
+fn follow(path: &[Cmd], f: &[Cmd], mut at: At) -> Option<At> {
+    for &c in f {
+        at = match (c, path.get(at.0)?) {
+            (Cmd::Turn(t), &Cmd::Turn(u)) if t == u && at.1 == 0 => (at.0 + 1, 0),
+            (Cmd::Fwd(v), &Cmd::Fwd(n)) if at.1 + v == n => (at.0 + 1, 0),
+            (Cmd::Fwd(v), &Cmd::Fwd(n)) if at.1 + v < n => (at.0, at.1 + v),
+            _ => return None,
+        };
+    }
+    Some(at)
+}
+
+// Characters `c` takes up in a routine, without the comma
+fn cmd_len(c: Cmd) -> usize {
+    match c {
+        Cmd::Turn(_) => 1,
+        Cmd::Fwd(v) => v.to_string().len(),
+    }
+}
+
+// Functions that could start at `at`, longest first. With `split` a function
+// can also end partway through a forward move, the rest is left for the next
+// one.
+fn candidates(path: &[Cmd], at: At, split: bool) -> Vec<Vec<Cmd>> {
+    let mut out = vec![];
+    let mut partial = vec![];
+    let mut f = vec![];
+    // Length of `f` as a line plus the comma after it
+    let mut len = 0;
+    for (i, &c) in path[at.0..].iter().enumerate() {
+        let c = match c {
+            Cmd::Fwd(n) if i == 0 => Cmd::Fwd(n - at.1),
+            c => c,
+        };
+        if let (Cmd::Fwd(n), true) = (c, split) {
+            for v in 1..n {
+                if len + cmd_len(Cmd::Fwd(v)) <= MAX_LINE {
+                    let mut g = f.clone();
+                    g.push(Cmd::Fwd(v));
+                    partial.push(g);
+                }
+            }
+        }
+        len += cmd_len(c) + 1;
+        if len - 1 > MAX_LINE {
+            break;
+        }
+        f.push(c);
+        out.push(f.clone());
+    }
+    out.reverse();
+    partial.reverse();
+    out.extend(partial);
+    out
+}
+
+fn search(
+    path: &[Cmd],
+    at: At,
+    split: bool,
+    main: &mut Vec<usize>,
+    fns: &mut Vec<Vec<Cmd>>,
+) -> bool {
+    if at == (path.len(), 0) {
+        return true;
+    }
+    // Every call takes a letter and a comma
+    if 2 * (main.len() + 1) - 1 > MAX_LINE {
+        return false;
+    }
+
+    for i in 0..fns.len() {
+        if let Some(next) = follow(path, &fns[i], at) {
+            main.push(i);
+            if search(path, next, split, main, fns) {
+                return true;
+            }
+            main.pop();
+        }
+    }
+
+    if fns.len() < MAX_FUNCTIONS {
+        for f in candidates(path, at, split) {
+            let next = follow(path, &f, at).unwrap();
+            main.push(fns.len());
+            fns.push(f);
+            if search(path, next, split, main, fns) {
+                return true;
+            }
+            fns.pop();
+            main.pop();
+        }
+    }
+    false
+}
+
+/// Main routine and the three functions it calls, with `split` forward
+/// moves can be split between functions. Functions the main routine doesn't
+/// need are a lone `L`.
+fn compress(path: &[Cmd], split: bool) -> Option<(String, Vec<String>)> {
+    let (mut main, mut fns) = (vec![], vec![]);
+    if !search(path, (0, 0), split, &mut main, &mut fns) {
+        return None;
+    }
+
+    let calls: Vec<String> = main
+        .iter()
+        .map(|&i| ((b'A' + i as u8) as char).to_string())
+        .collect();
+    let mut fns: Vec<String> = fns.iter().map(|f| cmds_to_line(f)).collect();
+    fns.resize(MAX_FUNCTIONS, "L".to_string());
+    Some((calls.join(","), fns))
+}
+
+fn moves_to_cmds(mut dir: Dir, moves: &[(Dir, i32)]) -> Vec<Cmd> {
+    let mut cmds = vec![];
 
     for &(ndir, val) in moves {
-        let turn = Turn::from_dirs(dir, ndir);
-        turns.push((turn, val));
+        // Only the first move can be straight ahead or back the way the
+        // robot faces, later ones always turn
+        match Turn::from_dirs(dir, ndir) {
+            Some(t) => cmds.push(Cmd::Turn(t)),
+            None if ndir == dir => {}
+            None => cmds.extend(&[Cmd::Turn(Turn::R), Cmd::Turn(Turn::R)]),
+        }
+        cmds.push(Cmd::Fwd(val));
         dir = ndir;
     }
 
-    turns
+    cmds
 }
 
-fn main() {
-    // Part 1
-    let mut tilemap: HashMap<Pos, Tile> = HashMap::new();
-    let mut droid = Droid::new();
-    {
-        let mut p = (0, 0);
-        while let Some(v) = droid.get_char() {
-            match v {
-                '\n' => p = (0, p.1 + 1),
-                c => {
-                    tilemap.insert(p, c.try_into().unwrap());
-                    p = (p.0 + 1, p.1);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorScaffold {
+    // Routes are planned from node to node
+    NotANode(Pos),
+}
+
+impl fmt::Display for ErrorScaffold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorScaffold::NotANode(p) => write!(
+                f,
+                "{},{} is partway along a straight run of scaffold",
+                p.0, p.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ErrorScaffold {}
+
+/// A straight run of scaffold between two nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Run {
+    from: usize,
+    to: usize,
+    // Heading when driving `from` -> `to`
+    dir: Dir,
+    len: i32,
+}
+
+/// The scaffold as a graph. Nodes are the ends, corners and intersections,
+/// where the robot may have to turn, and runs are the straight bits between
+/// them.
+#[derive(Debug, Clone)]
+struct Scaffold {
+    nodes: Vec<Pos>,
+    runs: Vec<Run>,
+    // Runs out of each node, both ways round
+    out: Vec<Vec<(usize, Run)>>,
+}
+
+impl Scaffold {
+    fn from_map(tilemap: &TileMap) -> Scaffold {
+        let on = |p: &Pos| matches!(tilemap.get(p), Some(Tile::Scaffold) | Some(Tile::Robot(_)));
+        let dirs = |p: Pos| -> Vec<Dir> {
+            get4w(p)
+                .iter()
+                .filter(|(_, q)| on(q))
+                .map(|(d, _)| *d)
+                .collect()
+        };
+
+        let mut nodes: Vec<Pos> = tilemap
+            .keys()
+            .filter(|p| on(p))
+            .filter(|&&p| match &dirs(p)[..] {
+                [a, b] => *a != b.opposite(),
+                _ => true,
+            })
+            .copied()
+            .collect();
+        nodes.sort_unstable();
+        let index: HashMap<Pos, usize> = nodes.iter().enumerate().map(|(i, &p)| (p, i)).collect();
+
+        let mut runs = vec![];
+        let mut out = vec![vec![]; nodes.len()];
+        for (from, &start) in nodes.iter().enumerate() {
+            for dir in dirs(start) {
+                let (mut p, mut len) = (dir.mv(start), 1);
+                while !index.contains_key(&p) {
+                    p = dir.mv(p);
+                    len += 1;
+                }
+                let run = Run {
+                    from,
+                    to: index[&p],
+                    dir,
+                    len,
+                };
+                // Found from both ends, keep the one from the lower node
+                if from < run.to {
+                    let back = Run {
+                        from: run.to,
+                        to: from,
+                        dir: dir.opposite(),
+                        len,
+                    };
+                    out[from].push((runs.len(), run));
+                    out[run.to].push((runs.len(), back));
+                    runs.push(run);
                 }
             }
         }
 
-        let v: i64 = tilemap
-            .iter()
-            .filter(|(_, &v)| v == Tile::Scaffold || v == Tile::Robot)
-            .filter(|(&k, _)| {
-                get4(k).iter().all(|k| {
-                    tilemap.get(k) == Some(&Tile::Robot) || tilemap.get(k) == Some(&Tile::Scaffold)
-                })
-            })
-            .map(|(&k, _)| k.0 * k.1)
-            .sum();
-        println!("Part 1: {}", v);
+        Scaffold { nodes, runs, out }
     }
 
-    // Part 2
-    {
-        let mut rpos: Pos = tilemap
+    /// The first way to drive over each run exactly once from `start` that
+    /// `f` makes something of. Ways are generated one at a time as moves,
+    /// there are far too many to keep. The robot only turns around before it
+    /// sets off, so intersections are crossed straight on or turned at.
+    /// Going straight on is tried first.
+    fn find_traversal<T>(
+        &self,
+        start: Pos,
+        facing: Dir,
+        mut f: impl FnMut(&[(Dir, i32)]) -> Option<T>,
+    ) -> Result<Option<T>, ErrorScaffold> {
+        let node = self
+            .nodes
             .iter()
-            .find(|(_, &v)| v == Tile::Robot)
-            .map(|(k, _)| *k)
-            .unwrap();
+            .position(|&p| p == start)
+            .ok_or(ErrorScaffold::NotANode(start))?;
+        let mut used = vec![false; self.runs.len()];
+        Ok(self.walk(
+            node,
+            facing,
+            self.runs.len(),
+            &mut used,
+            &mut vec![],
+            &mut f,
+        ))
+    }
 
-        let mut moves = vec![];
-        let mut dir = Dir::E;
-        let mut steps = 0;
-        loop {
-            let next = dir.mv(rpos);
-            if tilemap.get(&next) == Some(&Tile::Scaffold) {
-                rpos = next;
-                steps += 1;
-            } else {
-                moves.push((dir, steps));
-                steps = 0;
-
-                // Ugly:
-                if let Some(d) = get4w(rpos)
-                    .iter()
-                    .filter(|(d, _)| *d != dir.opposite())
-                    .find(|(_, p)| tilemap.get(p) == Some(&Tile::Scaffold))
-                {
-                    dir = d.0;
-                } else {
-                    break;
+    fn walk<T>(
+        &self,
+        node: usize,
+        dir: Dir,
+        left: usize,
+        used: &mut Vec<bool>,
+        moves: &mut Vec<(Dir, i32)>,
+        f: &mut impl FnMut(&[(Dir, i32)]) -> Option<T>,
+    ) -> Option<T> {
+        if left == 0 {
+            return f(moves);
+        }
+
+        let mut next: Vec<(usize, Run)> = self.out[node]
+            .iter()
+            .filter(|(i, r)| !used[*i] && (moves.is_empty() || r.dir != dir.opposite()))
+            .copied()
+            .collect();
+        next.sort_by_key(|(_, r)| r.dir != dir);
+        for (i, r) in next {
+            used[i] = true;
+            // Straight on through an intersection is one longer move
+            let merged = match moves.last_mut() {
+                Some(last) if last.0 == r.dir => {
+                    last.1 += r.len;
+                    true
                 }
+                _ => {
+                    moves.push((r.dir, r.len));
+                    false
+                }
+            };
+            let found = self.walk(r.to, r.dir, left - 1, used, moves, f);
+            if merged {
+                moves.last_mut().unwrap().1 -= r.len;
+            } else {
+                moves.pop();
+            }
+            used[i] = false;
+            if found.is_some() {
+                return found;
             }
         }
+        None
+    }
+}
 
-        let turns = moves_to_turns(&moves);
-        println!("{}", turns_to_cmd(&turns));
+const USAGE: &str = "usage: day17 [--feed] [--fps <n>] [--cast <file>]
 
-        let mut repeats = HashSet::new();
+    --feed         watch the robot's camera feed in the terminal
+    --fps <n>      frames per second of the feed (default 30)
+    --cast <file>  write the feed to an asciicast v2 file";
 
-        {
-            let rip = turns.clone();
-            for s in (3..7).rev() {
-                for b in 0..(rip.len() - s) {
-                    let sl = &rip[b..b + s];
+// How to show the camera feed, it's declined when there's nowhere to show it
+#[derive(Debug, Clone)]
+struct FeedOpts {
+    live: bool,
+    fps: u32,
+    cast: Option<String>,
+}
 
-                    let cnt: usize = rip.windows(s).filter(|w| *w == sl).count();
-                    if cnt > 1 {
-                        let txt = turns_to_cmd(sl);
-                        if txt.len() <= 20 {
-                            repeats.insert((sl.to_owned(), cnt * txt.len()));
-                        }
-                    }
-                }
+impl FeedOpts {
+    fn from_args() -> FeedOpts {
+        let usage = || -> ! {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        };
+        let mut opts = FeedOpts {
+            live: false,
+            fps: 30,
+            cast: None,
+        };
+        let mut args = env::args().skip(1);
+        while let Some(a) = args.next() {
+            match a.as_str() {
+                "--feed" => opts.live = true,
+                "--fps" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) if n > 0 => opts.fps = n,
+                    _ => usage(),
+                },
+                "--cast" => opts.cast = Some(args.next().unwrap_or_else(|| usage())),
+                _ => usage(),
             }
         }
+        opts
+    }
 
-        let mut repeats: Vec<_> = repeats.iter().collect();
-        repeats.sort_by_key(|a| a.1);
+    fn wanted(&self) -> bool {
+        self.live || self.cast.is_some()
+    }
+}
 
-        for r in &repeats {
-            println!("{}", turns_to_cmd(&r.0));
+fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
         }
+    }
+    out.push('"');
+    out
+}
 
-        // Solved by hand with the output printed from above
-        let mut tt = turns_to_cmd(&turns);
-        let a = "R,8,L,12,R,8";
-        let b = "L,12,L,12,L,10,R,10";
-        let c = "L,10,L,10,R,8";
-        tt = tt.replace(&a, "A");
-        tt = tt.replace(&b, "B");
-        tt = tt.replace(&c, "C");
-
-        let mut droid = Droid::new();
-        droid.start();
-        droid.write_fn(&tt);
-        droid.write_fn(a);
-        droid.write_fn(b);
-        droid.write_fn(c);
-        droid.write_fn("n");
-
-        while let Some(v) = droid.get_i64() {
-            let c: Result<u8, _> = v.try_into();
-            if let Ok(c) = c {
-                print!("{}", c as char);
-            } else {
-                print!("Part 2: {}", v);
+// Scaffold between two robot positions, the robot only moves in straight
+// lines between frames
+fn swept(a: Pos, b: Pos) -> Vec<Pos> {
+    if a.0 == b.0 {
+        (a.1.min(b.1)..=a.1.max(b.1)).map(|y| (a.0, y)).collect()
+    } else if a.1 == b.1 {
+        (a.0.min(b.0)..=a.0.max(b.0)).map(|x| (x, a.1)).collect()
+    } else {
+        vec![b]
+    }
+}
+
+// Play the frames the robot sends while it moves. It only reports the dust
+// at the end, so the scaffold it has been over stands in for it.
+fn watch_feed(droid: &mut Ascii, opts: &FeedOpts) {
+    droid
+        .read_until_prompt("Continuous video feed?\n\n")
+        .unwrap();
+
+    let mut cast = opts
+        .cast
+        .as_ref()
+        .map(|path| File::create(path).expect("Can't create the cast file"));
+    let delay = 1.0 / f64::from(opts.fps);
+    let mut cleaned = HashSet::new();
+    let mut last = None;
+    for n in 0.. {
+        let text = match droid.read_until_prompt("\n\n") {
+            Ok(text) => text,
+            // Only the dust count comes after the last frame
+            Err(ErrorAscii::NoPrompt) => break,
+            Err(e) => panic!("{}", e),
+        };
+        let frame = parse_map(text.trim_end());
+        let status = match robot(&frame) {
+            Some((p, t)) => {
+                cleaned.extend(swept(last.unwrap_or(p), p));
+                last = Some(p);
+                format!(
+                    "frame {}: robot at {},{} {}, {} scaffold cleaned",
+                    n,
+                    p.0,
+                    p.1,
+                    t.to_c(),
+                    cleaned.len()
+                )
             }
+            None => format!("frame {}: no robot", n),
+        };
+        let screen = format!("{}{}\n", map_text(&frame), status);
+
+        if opts.live {
+            print!("\x1b[H\x1b[2J{}", screen);
+            io::stdout().flush().unwrap();
+            thread::sleep(Duration::from_secs_f64(delay));
+        } else {
+            println!("{}", status);
+        }
+        if let Some(f) = cast.as_mut() {
+            if n == 0 {
+                let width = frame.keys().map(|p| p.0).max().unwrap_or(0) + 1;
+                let height = frame.keys().map(|p| p.1).max().unwrap_or(0) + 2;
+                writeln!(
+                    f,
+                    "{{\"version\": 2, \"width\": {}, \"height\": {}}}",
+                    width, height
+                )
+                .unwrap();
+            }
+            let screen = format!("\x1b[H\x1b[2J{}", screen.replace('\n', "\r\n"));
+            writeln!(f, "[{:.3}, \"o\", {}]", n as f64 * delay, json_str(&screen)).unwrap();
+        }
+    }
+}
+
+fn main() {
+    let opts = FeedOpts::from_args();
+
+    // Part 1
+    let mut droid = Ascii::new(Cpu::new());
+    let tilemap = parse_map(&droid.read_all_text().unwrap());
+    {
+        let on_scaffold =
+            |t: Option<&Tile>| matches!(t, Some(Tile::Scaffold) | Some(Tile::Robot(_)));
+        let v: i64 = tilemap
+            .iter()
+            .filter(|(_, v)| on_scaffold(Some(v)))
+            .filter(|(&k, _)| get4(k).iter().all(|k| on_scaffold(tilemap.get(k))))
+            .map(|(&k, _)| k.0 * k.1)
+            .sum();
+        println!("Part 1: {}", v);
+    }
+
+    // Part 2
+    {
+        let (rpos, facing) = match robot(&tilemap) {
+            Some((p, Tile::Robot(d))) => (p, d),
+            _ => panic!("No robot on the map"),
+        };
+
+        let scaffold = Scaffold::from_map(&tilemap);
+        // Splitting forward moves makes the search a lot slower, so that's
+        // the last resort
+        let (path, (main, fns)) = [false, true]
+            .iter()
+            .find_map(|&split| {
+                scaffold
+                    .find_traversal(rpos, facing, |moves| {
+                        let path = moves_to_cmds(facing, moves);
+                        compress(&path, split).map(|r| (path, r))
+                    })
+                    .unwrap_or_else(|e| panic!("{}", e))
+            })
+            .expect("No routines fit any path");
+        println!("{}", cmds_to_line(&path));
+
+        println!("Main: {}", main);
+        for (name, f) in "ABC".chars().zip(&fns) {
+            println!("{}: {}", name, f);
+        }
+
+        let mut cpu = Cpu::new();
+        cpu.mem[0] = 2;
+        let mut droid = Ascii::new(cpu);
+        droid.send_line(&main).unwrap();
+        for f in &fns {
+            droid.send_line(f).unwrap();
         }
-        println!();
+        droid
+            .send_line(if opts.wanted() { "y" } else { "n" })
+            .unwrap();
+
+        if opts.wanted() {
+            watch_feed(&mut droid, &opts);
+        } else {
+            print!("{}", droid.read_all_text().unwrap());
+        }
+        let dust = droid.numbers.pop_front().expect("No dust count");
+        if !droid.numbers.is_empty() {
+            panic!(
+                "Unexpected output after the dust count: {:?}",
+                droid.numbers
+            );
+        }
+        println!("Part 2: {}", dust);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+#######...#####
+#.....#...#...#
+#.....#...#...#
+......#...#...#
+......#...###.#
+......#.....#.#
+^########...#.#
+......#.#...#.#
+......#########
+........#...#..
+....#########..
+....#...#......
+....#...#......
+....#...#......
+....#####......";
+
+    fn parse_path(line: &str) -> Vec<Cmd> {
+        line.split(',')
+            .map(|c| match c {
+                "L" => Cmd::Turn(Turn::L),
+                "R" => Cmd::Turn(Turn::R),
+                n => Cmd::Fwd(n.parse().unwrap()),
+            })
+            .collect()
+    }
+
+    // Line for the path that `main` and `fns` drive, checking they fit
+    fn expand(main: &str, fns: &[&str]) -> String {
+        assert!(main.len() <= MAX_LINE && fns.len() <= MAX_FUNCTIONS);
+        assert!(fns.iter().all(|f| f.len() <= MAX_LINE));
+        let calls: Vec<&str> = main
+            .split(',')
+            .map(|c| fns[(c.as_bytes()[0] - b'A') as usize])
+            .collect();
+        calls.join(",")
+    }
+
+    const EXAMPLE_PATH: &str = "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2";
+
+    #[test]
+    fn scaffold_graph() {
+        let map = parse_map(EXAMPLE);
+        let scaffold = Scaffold::from_map(&map);
+        assert_eq!(scaffold.nodes.len(), 19);
+        assert_eq!(scaffold.runs.len(), 22);
+
+        // Every step between neighbouring scaffold is in exactly one run
+        let on = |p: &Pos| matches!(map.get(p), Some(Tile::Scaffold) | Some(Tile::Robot(_)));
+        let steps = map
+            .keys()
+            .filter(|p| on(p))
+            .map(|&p| [Dir::E, Dir::S].iter().filter(|d| on(&d.mv(p))).count())
+            .sum::<usize>();
+        let len: i32 = scaffold.runs.iter().map(|r| r.len).sum();
+        assert_eq!(len as usize, steps);
+
+        // The robot is at a dead end, with one run out to the first crossing
+        let start = scaffold.nodes.iter().position(|&p| p == (0, 6)).unwrap();
+        let out: Vec<(Pos, Dir, i32)> = scaffold.out[start]
+            .iter()
+            .map(|(_, r)| (scaffold.nodes[r.to], r.dir, r.len))
+            .collect();
+        assert_eq!(out, [((6, 6), Dir::E, 6)]);
+    }
+
+    #[test]
+    fn traversals() {
+        let scaffold = Scaffold::from_map(&parse_map(EXAMPLE));
+        let first = |facing| {
+            scaffold
+                .find_traversal((0, 6), facing, |moves| {
+                    Some(cmds_to_line(&moves_to_cmds(facing, moves)))
+                })
+                .unwrap()
+                .unwrap()
+        };
+        // Straight on at every crossing is the path from the puzzle
+        assert_eq!(first(Dir::N), EXAMPLE_PATH);
+        // The first move can be straight ahead, or behind the robot
+        assert_eq!(first(Dir::E), &EXAMPLE_PATH[2..]);
+        assert_eq!(first(Dir::W), format!("R,R,{}", &EXAMPLE_PATH[2..]));
+
+        let mut count = 0;
+        let none = scaffold.find_traversal((0, 6), Dir::N, |moves| {
+            let len: i32 = moves.iter().map(|m| m.1).sum();
+            assert_eq!(len, scaffold.runs.iter().map(|r| r.len).sum());
+            count += 1;
+            None::<()>
+        });
+        assert_eq!(none, Ok(None));
+        assert_eq!(count, 16);
+
+        assert_eq!(
+            scaffold.find_traversal((3, 6), Dir::N, |_| Some(())),
+            Err(ErrorScaffold::NotANode((3, 6)))
+        );
+    }
+
+    #[test]
+    fn compress_example() {
+        let (main, fns) = compress(&parse_path(EXAMPLE_PATH), false).unwrap();
+        let fns: Vec<&str> = fns.iter().map(|f| f.as_str()).collect();
+        assert_eq!(expand(&main, &fns), EXAMPLE_PATH);
+        // Any split that fits will do. The puzzle's uses shorter functions,
+        // the search tries the longest first.
+        assert_eq!(main, "A,B,C");
+        assert_eq!(
+            fns,
+            [
+                "R,8,R,8,R,4,R,4,R,8",
+                "L,6,L,2,R,4,R,4,R,8",
+                "R,8,R,8,L,6,L,2"
+            ]
+        );
+        let puzzle = ["R,8,R,8", "R,4,R,4,R,8", "L,6,L,2"];
+        assert_eq!(expand("A,B,C,B,A,C", &puzzle), EXAMPLE_PATH);
+
+        // Splitting forward moves can't make it any worse
+        let (main, fns) = compress(&parse_path(EXAMPLE_PATH), true).unwrap();
+        let fns: Vec<&str> = fns.iter().map(|f| f.as_str()).collect();
+        assert_eq!(expand(&main, &fns), EXAMPLE_PATH);
+    }
+
+    #[test]
+    fn compress_gives_up() {
+        // Functions of five `R,1` at most and ten calls cover 50 of them
+        let path = vec!["R,1"; 50].join(",");
+        assert!(compress(&parse_path(&path), false).is_some());
+        let path = vec!["R,1"; 51].join(",");
+        assert_eq!(compress(&parse_path(&path), false), None);
+
+        // Thirteen different moves need four functions of four
+        let moves: Vec<String> = (10..23)
+            .map(|n| format!("{},{}", if n % 2 == 0 { 'L' } else { 'R' }, n))
+            .collect();
+        let path = parse_path(&moves.join(","));
+        assert_eq!(
+            compress(&path[..24], false).map(|r| r.0),
+            Some("A,B,C".to_string())
+        );
+        assert_eq!(compress(&path, false), None);
+        assert_eq!(compress(&path, true), None);
     }
 }
@@ -0,0 +1,38 @@
+// Key collection on the real input, run with `cargo bench -p day18`
+
+use std::time::{Duration, Instant};
+
+use day18::{parse_map, split_entrance, KeyGraph, Pos, TileMap};
+
+const ROUNDS: u32 = 20;
+
+fn time<T>(mut f: impl FnMut() -> T) -> (Duration, T) {
+    let out = f();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    (start.elapsed() / ROUNDS, out)
+}
+
+fn bench(name: &str, map: &TileMap, robots: &[Pos], solve: fn(&KeyGraph) -> Option<usize>) {
+    let (build, graph) = time(|| KeyGraph::new(map, robots));
+    let (search, steps) = time(|| solve(&graph));
+    println!(
+        "{:<8} {:>6}  graph {:>10.3?}  search {:>10.3?}",
+        name,
+        steps.expect("No way to get every key"),
+        build,
+        search
+    );
+}
+
+fn main() {
+    let path = format!("{}/input", env!("CARGO_MANIFEST_DIR"));
+    let text = std::fs::read_to_string(&path).expect("Can't read day input");
+    let (mut map, robots) = parse_map(&text);
+
+    bench("part 1", &map, &robots, KeyGraph::collect);
+    let robots = split_entrance(&mut map, robots[0]);
+    bench("part 2", &map, &robots, KeyGraph::collect_all);
+}
@@ -0,0 +1,319 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::convert::{TryFrom, TryInto};
+
+pub type Pos = (i32, i32);
+pub type TileMap = HashMap<Pos, Tile>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tile {
+    Floor,
+    Wall,
+    Door(char),
+    Key(char),
+}
+
+impl TryFrom<char> for Tile {
+    type Error = ();
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        let out = match c {
+            '#' => Tile::Wall,
+            '.' | '@' => Tile::Floor,
+            c if c.is_ascii_lowercase() => Tile::Key(c),
+            c if c.is_ascii_uppercase() => Tile::Door(c),
+            _ => return Err(()),
+        };
+        Ok(out)
+    }
+}
+
+/// The map and where the robots start
+pub fn parse_map(text: &str) -> (TileMap, Vec<Pos>) {
+    let mut tilemap = HashMap::new();
+    let mut robots = vec![];
+    for (y, line) in text.lines().enumerate() {
+        for (x, c) in line.chars().enumerate() {
+            let (x, y) = (x as i32, y as i32);
+            tilemap.insert((x, y), c.try_into().unwrap());
+            if c == '@' {
+                robots.push((x, y));
+            }
+        }
+    }
+    (tilemap, robots)
+}
+
+/// Wall off the entrance at `pos` for part 2, returns the four robots
+/// around it
+pub fn split_entrance(tilemap: &mut TileMap, pos: Pos) -> [Pos; 4] {
+    for &(dx, dy) in &[(0, 0), (0, 1), (0, -1), (1, 0), (-1, 0)] {
+        tilemap.insert((pos.0 + dx, pos.1 + dy), Tile::Wall);
+    }
+    [
+        (pos.0 + 1, pos.1 + 1),
+        (pos.0 + 1, pos.1 - 1),
+        (pos.0 - 1, pos.1 - 1),
+        (pos.0 - 1, pos.1 + 1),
+    ]
+}
+
+fn key_bit(k: char) -> u32 {
+    1 << (k.to_ascii_lowercase() as u8 - b'a')
+}
+
+/// The shortest walk from one node of a `KeyGraph` to a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub to: usize,
+    pub dist: usize,
+    // Keys for the doors on the way
+    pub doors: u32,
+    // Keys on the way, picked up in passing
+    pub keys: u32,
+}
+
+// Shortest walk to every key from `from`. Doors are walked through rather
+// than around, these mazes never have another way past one.
+fn reach(map: &TileMap, from: Pos) -> Vec<Edge> {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+    let mut out = vec![];
+    seen.insert(from);
+    queue.push_back((from, 0, 0, 0));
+
+    while let Some((p, dist, doors, keys)) = queue.pop_front() {
+        let (doors, keys) = match map[&p] {
+            Tile::Door(d) => (doors | key_bit(d), keys),
+            Tile::Key(k) if p != from => {
+                out.push(Edge {
+                    to: (k as u8 - b'a') as usize,
+                    dist,
+                    doors,
+                    keys,
+                });
+                (doors, keys | key_bit(k))
+            }
+            _ => (doors, keys),
+        };
+        for &(dx, dy) in &[(0, 1), (0, -1), (1, 0), (-1, 0)] {
+            let n = (p.0 + dx, p.1 + dy);
+            if map.get(&n).is_some_and(|&t| t != Tile::Wall) && seen.insert(n) {
+                queue.push_back((n, dist + 1, doors, keys));
+            }
+        }
+    }
+    out
+}
+
+/// The maze boiled down to distances between keys, and from the robots to
+/// the keys
+#[derive(Debug, Clone)]
+pub struct KeyGraph {
+    // Nodes 0..26 are the keys a to z, the robots' starts come after
+    pub edges: Vec<Vec<Edge>>,
+    pub starts: Vec<usize>,
+    pub all: u32,
+}
+
+impl KeyGraph {
+    pub fn new(map: &TileMap, robots: &[Pos]) -> KeyGraph {
+        let mut edges = vec![vec![]; 26 + robots.len()];
+        let mut all = 0;
+        for (&p, &t) in map {
+            if let Tile::Key(k) = t {
+                edges[(k as u8 - b'a') as usize] = reach(map, p);
+                all |= key_bit(k);
+            }
+        }
+        for (i, &p) in robots.iter().enumerate() {
+            edges[26 + i] = reach(map, p);
+        }
+
+        KeyGraph {
+            edges,
+            starts: (26..26 + robots.len()).collect(),
+            all,
+        }
+    }
+
+    // Keys worth heading to from `node` holding `keys`, with the distance
+    // and the keys held after
+    fn moves(&self, node: usize, keys: u32) -> impl Iterator<Item = (usize, usize, u32)> + '_ {
+        self.edges[node]
+            .iter()
+            .filter(move |e| keys & (1 << e.to) == 0 && e.doors & !keys == 0)
+            .map(move |e| (e.to, e.dist, keys | e.keys | (1 << e.to)))
+    }
+
+    /// Fewest steps for the first robot to pick up every key
+    pub fn collect(&self) -> Option<usize> {
+        let start = (self.starts[0], 0);
+        let mut best: HashMap<(usize, u32), usize> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        best.insert(start, 0);
+        heap.push(Reverse((0, start)));
+
+        while let Some(Reverse((dist, (node, keys)))) = heap.pop() {
+            if keys == self.all {
+                return Some(dist);
+            }
+            if best.get(&(node, keys)).is_some_and(|&d| d < dist) {
+                continue;
+            }
+            for (next, d, keys) in self.moves(node, keys) {
+                let nd = dist + d;
+                if best.get(&(next, keys)).is_none_or(|&b| nd < b) {
+                    best.insert((next, keys), nd);
+                    heap.push(Reverse((nd, (next, keys))));
+                }
+            }
+        }
+        None
+    }
+
+    /// Fewest steps for the robots to pick up every key between them, only
+    /// one of them moves at a time
+    pub fn collect_all(&self) -> Option<usize> {
+        let start = (self.starts.clone(), 0);
+        let mut best: HashMap<(Vec<usize>, u32), usize> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        best.insert(start.clone(), 0);
+        heap.push(Reverse((0, start)));
+
+        while let Some(Reverse((dist, (at, keys)))) = heap.pop() {
+            if keys == self.all {
+                return Some(dist);
+            }
+            if best.get(&(at.clone(), keys)).is_some_and(|&d| d < dist) {
+                continue;
+            }
+            for (robot, &node) in at.iter().enumerate() {
+                for (next, d, keys) in self.moves(node, keys) {
+                    let mut at = at.clone();
+                    at[robot] = next;
+                    let state = (at, keys);
+                    let nd = dist + d;
+                    if best.get(&state).is_none_or(|&b| nd < b) {
+                        best.insert(state.clone(), nd);
+                        heap.push(Reverse((nd, state)));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(map: &str) -> KeyGraph {
+        let (tilemap, robots) = parse_map(map);
+        KeyGraph::new(&tilemap, &robots)
+    }
+
+    fn steps(map: &str) -> usize {
+        graph(map).collect_all().unwrap()
+    }
+
+    // Part 1, which has to agree with the search for any number of robots
+    fn steps_one(map: &str) -> usize {
+        let graph = graph(map);
+        let steps = graph.collect().unwrap();
+        assert_eq!(graph.collect_all(), Some(steps));
+        steps
+    }
+
+    #[test]
+    fn four_robots() {
+        let map = "\
+#######
+#a.#Cd#
+##@#@##
+#######
+##@#@##
+#cB#Ab#
+#######";
+        assert_eq!(steps(map), 8);
+
+        let map = "\
+###############
+#d.ABC.#.....a#
+######@#@######
+###############
+######@#@######
+#b.....#.....c#
+###############";
+        assert_eq!(steps(map), 24);
+
+        let map = "\
+#############
+#DcBa.#.GhKl#
+#.###@#@#I###
+#e#d#####j#k#
+###C#@#@###J#
+#fEbA.#.FgHi#
+#############";
+        assert_eq!(steps(map), 32);
+
+        let map = "\
+#############
+#g#f.D#..h#l#
+#F###e#E###.#
+#dCba@#@BcIJ#
+#############
+#nK.L@#@G...#
+#M###N#H###.#
+#o#m..#i#jk.#
+#############";
+        assert_eq!(steps(map), 72);
+    }
+
+    #[test]
+    fn one_robot() {
+        let map = "\
+#########
+#b.A.@.a#
+#########";
+        assert_eq!(steps_one(map), 8);
+
+        let map = "\
+########################
+#f.D.E.e.C.b.A.@.a.B.c.#
+######################.#
+#d.....................#
+########################";
+        assert_eq!(steps_one(map), 86);
+
+        let map = "\
+########################
+#...............b.C.D.f#
+#.######################
+#.....@.a.B.c.d.A.e.F.g#
+########################";
+        assert_eq!(steps_one(map), 132);
+
+        let map = "\
+#################
+#i.G..c...e..H.p#
+########.########
+#j.A..b...f..D.o#
+########@########
+#k.E..a...g..B.n#
+########.########
+#l.F..d...h..C.m#
+#################";
+        assert_eq!(steps_one(map), 136);
+
+        let map = "\
+########################
+#@..............ac.GI.b#
+###d#e#f################
+###A#B#C################
+###g#h#i################
+########################";
+        assert_eq!(steps_one(map), 81);
+    }
+}
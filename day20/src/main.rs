@@ -35,11 +35,8 @@ fn movable(map: &TileMap, p: Pos) -> impl Iterator<Item = Pos> + '_ {
         .iter()
         .map(move |&s| (s.0 + p.0, s.1 + p.1))
         .filter(move |s| {
-            let t = map[&s];
-            match t {
-                Tile::Passage => true,
-                _ => false,
-            }
+            let t = map[s];
+            matches!(t, Tile::Passage)
         })
 }
 
@@ -62,11 +59,11 @@ fn find(map: &TileMap, pmap: &PortalMap, start: Pos, finish: Pos) -> Option<i32>
                 }
             }
 
-            let portal = pmap.values().find(|v| v.contains(&p));
+            let portal = pmap.values().find(|v| v.contains(p));
             if let Some(por) = portal {
                 let exit = por.iter().find(|e| **e != *p);
                 if let Some(e) = exit {
-                    if !dmap.contains(&e) && !cur.contains(&e) {
+                    if !dmap.contains(e) && !cur.contains(e) {
                         cur.push(*e);
                     }
                 }
@@ -147,6 +144,7 @@ fn find_depth(map: &TileMap, pmap: &PortalMap, start: Pos, finish: Pos) -> Optio
     None
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Copy)]
 struct Portal {
     a: Pos,
@@ -188,7 +186,7 @@ fn find_portals(tilemap: &TileMap) -> PortalMap {
 
     for (p, a, b) in portals {
         let id = (a, b);
-        let v = pmap.entry(id).or_insert_with(Vec::new);
+        let v = pmap.entry(id).or_default();
         v.push(p);
     }
 
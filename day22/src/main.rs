@@ -8,6 +8,7 @@ enum Action {
     DealWithInc(usize),
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
 enum ParseActionError {
     Int(ParseIntError),
@@ -92,7 +93,7 @@ fn cut_revi(deck_len: usize, n: i32, i: usize) -> usize {
         let n = n as usize;
         (i + n) % deck_len
     } else if n < 0 {
-        let n = n.abs() as usize;
+        let n = n.unsigned_abs() as usize;
         (i + deck_len - n) % deck_len
     } else {
         i
@@ -136,7 +137,7 @@ fn cut_i(deck_len: usize, n: i32, i: usize) -> usize {
         let n = n as usize;
         (i + (deck_len - n)) % deck_len
     } else if n < 0 {
-        let n = n.abs() as usize;
+        let n = n.unsigned_abs() as usize;
         (i + n) % deck_len
     } else {
         i
@@ -246,8 +247,8 @@ fn fwd(n: usize) -> usize {
 
 fn rev(n: usize) -> usize {
     let n = n as u128;
-    let og: u128 = 85834995146770 as u128;
-    let j: u128 = 80725416546647 as u128;
+    let og: u128 = 85834995146770_u128;
+    let j: u128 = 80725416546647_u128;
     let s = SIZE as u128;
 
     ((og + n * j) % s) as usize
@@ -255,8 +256,8 @@ fn rev(n: usize) -> usize {
 
 fn rev_mil(n: usize) -> usize {
     let n = n as u128;
-    let og: u128 = 115753313030480 as u128;
-    let j: u128 = 63912695741104 as u128;
+    let og: u128 = 115753313030480_u128;
+    let j: u128 = 63912695741104_u128;
     let s = SIZE as u128;
 
     ((og + n * j) % s) as usize
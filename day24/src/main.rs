@@ -66,13 +66,14 @@ fn is_wrap(p: i32) -> bool {
 }
 
 fn neighbors3(area: &HashMap<Pos3, C>, p: Pos3) -> Vec<C> {
+    #[allow(clippy::enum_variant_names)]
     #[derive(Copy, Clone)]
     enum D {
         L,
         R,
         U,
         D,
-    };
+    }
 
     // 2d vector + direction to know where to enter inner level from
     let nlist = [(0, 1, D::D), (0, -1, D::U), (1, 0, D::R), (-1, 0, D::L)];
@@ -195,7 +196,7 @@ fn p1() {
 
         let mut keys: Vec<_> = area.keys().collect();
         keys.sort();
-        let sorted: Vec<_> = keys.iter().map(|k| area[&k]).collect();
+        let sorted: Vec<_> = keys.iter().map(|k| area[k]).collect();
 
         if past.contains_key(&sorted) {
             let p1 = calc_biod(&area);
@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use intcode::fuzz::{check, Case};
+
+fuzz_target!(|data: &[u8]| {
+    if let Err(d) = check(&Case::from_bytes(data)) {
+        panic!("{}", d);
+    }
+});
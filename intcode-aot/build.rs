@@ -0,0 +1,42 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use intcode::{parse_mem, transpile};
+
+// With the words each day writes before running: day 2's noun and verb, and
+// the free play switch of days 13 and 17
+const DAYS: &[(&str, &[usize])] = &[
+    ("day02", &[1, 2]),
+    ("day05", &[]),
+    ("day07", &[]),
+    ("day09", &[]),
+    ("day11", &[]),
+    ("day13", &[0]),
+    ("day15", &[]),
+    ("day17", &[0]),
+    ("day19", &[]),
+    ("day21", &[]),
+    ("day23", &[]),
+    ("day25", &[]),
+];
+
+// OUT #7; HLT with every word preset, so none of it is baked in
+const DYNAMIC: &str = "104,7,99";
+
+fn write(out: &str, name: &str, mem: &[i64], preset: &[usize]) {
+    let src = transpile::transpile(mem, preset).unwrap_or_else(|e| panic!("{}: {}", name, e));
+    fs::write(Path::new(out).join(format!("{}.rs", name)), src).unwrap();
+}
+
+fn main() {
+    let out = env::var("OUT_DIR").unwrap();
+    for (day, preset) in DAYS {
+        let input = format!("../{}/input", day);
+        println!("cargo:rerun-if-changed={}", input);
+
+        let mem = parse_mem(&fs::read_to_string(&input).unwrap());
+        write(&out, day, &mem, preset);
+    }
+    write(&out, "dynamic", &parse_mem(DYNAMIC), &[0, 1, 2]);
+}
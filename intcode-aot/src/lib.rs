@@ -0,0 +1,55 @@
+// Each module has a `Program` with the same `run` as `intcode::Cpu`, see
+// `intcode transpile`
+
+pub mod day02 {
+    include!(concat!(env!("OUT_DIR"), "/day02.rs"));
+}
+
+pub mod day05 {
+    include!(concat!(env!("OUT_DIR"), "/day05.rs"));
+}
+
+pub mod day07 {
+    include!(concat!(env!("OUT_DIR"), "/day07.rs"));
+}
+
+pub mod day09 {
+    include!(concat!(env!("OUT_DIR"), "/day09.rs"));
+}
+
+pub mod day11 {
+    include!(concat!(env!("OUT_DIR"), "/day11.rs"));
+}
+
+pub mod day13 {
+    include!(concat!(env!("OUT_DIR"), "/day13.rs"));
+}
+
+pub mod day15 {
+    include!(concat!(env!("OUT_DIR"), "/day15.rs"));
+}
+
+pub mod day17 {
+    include!(concat!(env!("OUT_DIR"), "/day17.rs"));
+}
+
+pub mod day19 {
+    include!(concat!(env!("OUT_DIR"), "/day19.rs"));
+}
+
+pub mod day21 {
+    include!(concat!(env!("OUT_DIR"), "/day21.rs"));
+}
+
+pub mod day23 {
+    include!(concat!(env!("OUT_DIR"), "/day23.rs"));
+}
+
+pub mod day25 {
+    include!(concat!(env!("OUT_DIR"), "/day25.rs"));
+}
+
+// A tiny program the caller may rewrite entirely
+pub mod dynamic {
+    include!(concat!(env!("OUT_DIR"), "/dynamic.rs"));
+}
@@ -0,0 +1,178 @@
+use intcode::{Cpu, Memory, Status};
+use intcode_aot::*;
+
+// Feed `input` in order and collect every status until halt, a fault or the
+// input running out
+fn drive(mut run: impl FnMut(&mut Option<i64>) -> Status, input: &[i64]) -> Vec<Status> {
+    let mut input = input.iter().copied();
+    let mut feed = None;
+    let mut seen = vec![];
+    loop {
+        let status = run(&mut feed);
+        seen.push(status);
+        match status {
+            Status::Output(_) => {}
+            Status::NeedsInput => match input.next() {
+                Some(v) => feed = Some(v),
+                None => return seen,
+            },
+            Status::Halted | Status::Fault { .. } | Status::LimitExceeded { .. } => return seen,
+        }
+    }
+}
+
+fn ascii(s: &str) -> Vec<i64> {
+    s.bytes().map(|b| b as i64).collect()
+}
+
+fn no_setup(_: &mut Memory) {}
+
+fn free_play(mem: &mut Memory) {
+    mem[0] = 2;
+}
+
+// Run the interpreter and the transpiled program side by side, returns
+// where the transpiled one had to fall back to the interpreter, if it did
+macro_rules! check {
+    ($day:ident, $setup:expr, $input:expr) => {{
+        let setup: fn(&mut Memory) = $setup;
+        let input: Vec<i64> = $input;
+
+        let mut cpu = Cpu::with_mem($day::PROG.to_vec());
+        setup(&mut cpu.mem);
+        let expected = drive(|i| cpu.run(i), &input);
+
+        let mut prog = $day::Program::new();
+        setup(&mut prog.mem);
+        let actual = drive(|i| prog.run(i), &input);
+
+        assert_eq!(expected, actual, "{} statuses differ", stringify!($day));
+        assert_eq!(
+            cpu.mem.as_slice(),
+            prog.memory().as_slice(),
+            "{} memory differs",
+            stringify!($day)
+        );
+        prog.fell_back_at()
+    }};
+}
+
+#[test]
+fn day02() {
+    assert_eq!(check!(day02, no_setup, vec![]), None);
+    // The noun and verb are preset, the first instruction reads them from
+    // memory
+    assert_eq!(
+        check!(
+            day02,
+            |m| {
+                m[1] = 12;
+                m[2] = 2;
+            },
+            vec![]
+        ),
+        None
+    );
+}
+
+#[test]
+fn day05() {
+    // The system id is added to the opcode at 6, making it an `ADD` for the
+    // air conditioner and a `JIT` for the radiator
+    assert_eq!(check!(day05, no_setup, vec![1]), Some(6));
+    assert_eq!(check!(day05, no_setup, vec![5]), Some(6));
+}
+
+#[test]
+fn day07() {
+    // The phase picks the amplifier through a jump table
+    for phase in 0..10 {
+        assert_eq!(check!(day07, no_setup, vec![phase, 0]), None);
+    }
+    assert_eq!(check!(day07, no_setup, vec![7, 0, 12, 5, 9]), None);
+}
+
+#[test]
+fn day09() {
+    assert_eq!(check!(day09, no_setup, vec![1]), None);
+    assert_eq!(check!(day09, no_setup, vec![2]), None);
+}
+
+#[test]
+fn day11() {
+    let colors = (0..500).map(|i| (i / 3) % 2).collect();
+    assert_eq!(check!(day11, no_setup, colors), None);
+}
+
+#[test]
+fn day13() {
+    assert_eq!(check!(day13, no_setup, vec![]), None);
+    let joystick = (0..300).map(|i| (i % 3) - 1).collect();
+    // Free play rewrites the first opcode, which runs in the interpreter
+    assert_eq!(check!(day13, free_play, joystick), None);
+}
+
+#[test]
+fn day15() {
+    let moves = (0..400).map(|i| (i * 7 / 3) % 4 + 1).collect();
+    assert_eq!(check!(day15, no_setup, moves), None);
+}
+
+#[test]
+fn day17() {
+    assert_eq!(check!(day17, no_setup, vec![]), None);
+    let routine = "A,B\nL,4\nR,6,L,2\nL\nn\n";
+    // Same as day13
+    assert_eq!(check!(day17, free_play, ascii(routine)), None);
+}
+
+#[test]
+fn day19() {
+    for y in 0..8 {
+        for x in 0..8 {
+            assert_eq!(check!(day19, no_setup, vec![x, y]), None);
+        }
+    }
+}
+
+#[test]
+fn day21() {
+    let walk = ascii("NOT A J\nNOT C T\nAND D T\nOR T J\nWALK\n");
+    assert_eq!(check!(day21, no_setup, walk), None);
+    assert_eq!(check!(day21, no_setup, ascii("NOT A J\nRUN\n")), None);
+}
+
+#[test]
+fn day23() {
+    // The address picks the computer's code through a jump table
+    for addr in [0, 17, 49] {
+        let mut input = vec![addr];
+        input.extend(vec![-1; 50]);
+        assert_eq!(check!(day23, no_setup, input), None);
+    }
+}
+
+#[test]
+fn day25() {
+    let cmds = "north\nsouth\neast\ntake nothing\nwest\nsouth\ninv\nwest\nnorth\n";
+    assert_eq!(check!(day25, no_setup, ascii(cmds)), None);
+}
+
+#[test]
+fn dynamic() {
+    assert_eq!(check!(dynamic, no_setup, vec![]), None);
+    // OUT [2]
+    assert_eq!(
+        check!(
+            dynamic,
+            |m| {
+                m[0] = 4;
+                m[1] = 2;
+            },
+            vec![]
+        ),
+        None
+    );
+    // HLT straight away
+    assert_eq!(check!(dynamic, |m| m[0] = 99, vec![]), None);
+}
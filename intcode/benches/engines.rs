@@ -0,0 +1,62 @@
+// Interpreter vs cached engine, run with `cargo bench -p intcode`
+
+use std::time::{Duration, Instant};
+
+use intcode::{parse_mem, Cpu, Engine, Status};
+
+const ROUNDS: u32 = 10;
+
+fn program(day: &str) -> Vec<i64> {
+    let path = format!("{}/../{}/input", env!("CARGO_MANIFEST_DIR"), day);
+    parse_mem(&std::fs::read_to_string(&path).expect("Can't read day input"))
+}
+
+// Feed `input` in order and collect every output until halt or it runs dry
+fn run(mem: &[i64], engine: Engine, input: &[i64]) -> Vec<i64> {
+    let mut cpu = Cpu::with_engine(mem.to_vec(), engine);
+    let mut input = input.iter().copied();
+    let mut feed = None;
+    let mut out = vec![];
+    loop {
+        match cpu.run(&mut feed) {
+            Status::Output(v) => out.push(v),
+            Status::NeedsInput => match input.next() {
+                Some(v) => feed = Some(v),
+                None => return out,
+            },
+            Status::Halted => return out,
+            s => panic!("Engine stopped: {:?}", s),
+        }
+    }
+}
+
+fn time(mem: &[i64], engine: Engine, input: &[i64]) -> (Duration, Vec<i64>) {
+    let out = run(mem, engine, input);
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        run(mem, engine, input);
+    }
+    (start.elapsed() / ROUNDS, out)
+}
+
+fn bench(name: &str, mem: &[i64], input: &[i64]) {
+    let (interp, expected) = time(mem, Engine::Interp, input);
+    let (cached, out) = time(mem, Engine::Cached, input);
+    assert_eq!(expected, out, "{}: engines disagree", name);
+    println!(
+        "{:<16} interp {:>10.3?}  cached {:>10.3?}  {:.2}x",
+        name,
+        interp,
+        cached,
+        interp.as_secs_f64() / cached.as_secs_f64()
+    );
+}
+
+fn main() {
+    bench("day09 BOOST", &program("day09"), &[2]);
+
+    // Wander around the ship a bit, every line goes through the parser
+    let walk = "north\nsouth\neast\nwest\nsouth\nnorth\ninv\nwest\neast\n";
+    let cmds: Vec<i64> = walk.repeat(20).bytes().map(|b| b as i64).collect();
+    bench("day25 walk", &program("day25"), &cmds);
+}
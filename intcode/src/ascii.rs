@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::{Cpu, Fault, Limit, Status};
+
+/// What to do with outputs in 128..=255, bytes that aren't ASCII
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Invalid {
+    // Treat them as numbers like anything else outside ASCII
+    Numeric,
+    // Decode as Latin-1
+    Latin1,
+    // U+FFFD
+    Replace,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorAscii {
+    NotAscii(char),
+    InvalidByte(i64),
+    // The cpu halted or wanted input before printing the prompt
+    NoPrompt,
+    Fault { pc: usize, kind: Fault },
+    LimitExceeded { pc: usize, limit: Limit },
+}
+
+impl fmt::Display for ErrorAscii {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorAscii::NotAscii(c) => write!(f, "can't send `{}`, it isn't ASCII", c),
+            ErrorAscii::InvalidByte(v) => write!(f, "output {} isn't ASCII", v),
+            ErrorAscii::NoPrompt => write!(f, "program stopped before the prompt"),
+            ErrorAscii::Fault { pc, kind } => write!(f, "fault at {}: {:?}", pc, kind),
+            ErrorAscii::LimitExceeded { pc, limit } => {
+                write!(f, "{:?} limit hit at {}", limit, pc)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ErrorAscii {}
+
+/// Line based text I/O over a `Cpu`. Outputs that aren't ASCII go to
+/// `numbers` instead of the text.
+#[derive(Debug, Clone)]
+pub struct Ascii {
+    pub cpu: Cpu,
+    pub invalid: Invalid,
+    pub numbers: VecDeque<i64>,
+    text: String,
+    input: VecDeque<i64>,
+    halted: bool,
+}
+
+impl Ascii {
+    pub fn new(cpu: Cpu) -> Ascii {
+        Ascii {
+            cpu,
+            invalid: Invalid::Numeric,
+            numbers: VecDeque::new(),
+            text: String::new(),
+            input: VecDeque::new(),
+            halted: false,
+        }
+    }
+
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Queue `line` for the program, a newline is added if it doesn't end
+    /// with one. Nothing runs until output is read.
+    pub fn send_line(&mut self, line: &str) -> Result<(), ErrorAscii> {
+        if let Some(c) = line.chars().find(|c| !c.is_ascii()) {
+            return Err(ErrorAscii::NotAscii(c));
+        }
+        self.input.extend(line.bytes().map(i64::from));
+        if !line.ends_with('\n') {
+            self.input.push_back(i64::from(b'\n'));
+        }
+        Ok(())
+    }
+
+    fn push_output(&mut self, v: i64) -> Result<(), ErrorAscii> {
+        match (v, self.invalid) {
+            (0..=127, _) | (128..=255, Invalid::Latin1) => self.text.push(v as u8 as char),
+            (128..=255, Invalid::Replace) => self.text.push(char::REPLACEMENT_CHARACTER),
+            (128..=255, Invalid::Error) => return Err(ErrorAscii::InvalidByte(v)),
+            _ => self.numbers.push_back(v),
+        }
+        Ok(())
+    }
+
+    // Run until the next output, false once the cpu halts or runs out of
+    // queued input. Input the cpu stopped before using goes back in the
+    // queue.
+    fn pull(&mut self) -> Result<bool, ErrorAscii> {
+        let mut feed = None;
+        loop {
+            let status = self.cpu.run(&mut feed);
+            if let Some(v) = feed.take() {
+                self.input.push_front(v);
+            }
+            match status {
+                Status::Output(v) => {
+                    self.push_output(v)?;
+                    return Ok(true);
+                }
+                Status::NeedsInput => match self.input.pop_front() {
+                    Some(v) => feed = Some(v),
+                    None => return Ok(false),
+                },
+                Status::Halted => {
+                    self.halted = true;
+                    return Ok(false);
+                }
+                Status::Fault { pc, kind } => return Err(ErrorAscii::Fault { pc, kind }),
+                Status::LimitExceeded { pc, limit } => {
+                    return Err(ErrorAscii::LimitExceeded { pc, limit })
+                }
+            }
+        }
+    }
+
+    /// Text up to and including `prompt`, anything after it is kept for the
+    /// next read
+    pub fn read_until_prompt(&mut self, prompt: &str) -> Result<String, ErrorAscii> {
+        let mut found = self.text.find(prompt);
+        while found.is_none() {
+            if !self.pull()? {
+                return Err(ErrorAscii::NoPrompt);
+            }
+            if self.text.ends_with(prompt) {
+                found = Some(self.text.len() - prompt.len());
+            }
+        }
+        let end = found.unwrap() + prompt.len();
+        Ok(self.text.drain(..end).collect())
+    }
+
+    /// All text until the cpu halts or needs more input than is queued
+    pub fn read_all_text(&mut self) -> Result<String, ErrorAscii> {
+        while self.pull()? {}
+        Ok(std::mem::take(&mut self.text))
+    }
+}
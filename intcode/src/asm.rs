@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{Intcode, Mode};
+
+// Syntax, one statement per line:
+//
+//     ; comment
+//     loop:   IN  rb+1              ; label, then instruction
+//             ADD [x], #-1, [x]
+//             JIT [x], #loop
+//             HLT
+//     x:      .data 10
+//     msg:    .data "Hi\n", 0
+//
+// Operands are `#imm`, `[pos]` or `rb+off`, values are integers, labels,
+// character literals like 'A' or sums of those (`end-1`). `DATA n` and lines
+// starting with `addr: raw,words` from the disassembler listing are accepted
+// too so a listing assembles back to the program.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorAsm {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for ErrorAsm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for ErrorAsm {}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Num(i64),
+    Label(String),
+}
+
+// Sum of signed terms
+type Expr = Vec<(i64, Term)>;
+
+#[derive(Debug)]
+enum Item {
+    Op(Intcode, Vec<Expr>),
+    Data(Vec<Expr>),
+}
+
+fn err<T>(line: usize, msg: impl Into<String>) -> Result<T, ErrorAsm> {
+    Err(ErrorAsm {
+        line,
+        msg: msg.into(),
+    })
+}
+
+pub fn mnemonic_op(name: &str, modes: &[Mode]) -> Option<Intcode> {
+    let m = |i: usize| modes[i];
+    let op = match (name.to_ascii_uppercase().as_str(), modes.len()) {
+        ("ADD", 3) => Intcode::Add(m(0), m(1), m(2)),
+        ("MUL", 3) => Intcode::Mult(m(0), m(1), m(2)),
+        ("IN", 1) => Intcode::In(m(0)),
+        ("OUT", 1) => Intcode::Out(m(0)),
+        ("JIT", 2) => Intcode::Jit(m(0), m(1)),
+        ("JIF", 2) => Intcode::Jif(m(0), m(1)),
+        ("LT", 3) => Intcode::Lt(m(0), m(1), m(2)),
+        ("EQ", 3) => Intcode::Equ(m(0), m(1), m(2)),
+        ("ARB", 1) => Intcode::Adj(m(0)),
+        ("HLT", 0) => Intcode::Halt,
+        _ => return None,
+    };
+    Some(op)
+}
+
+// Strip a `;` comment, ignoring ones inside quotes
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == ';' => return &line[..i],
+            None => {}
+        }
+    }
+    line
+}
+
+// Drop the `addr: raw,words` columns of a disassembler listing line
+fn strip_listing(line: &str) -> &str {
+    let t = line.trim_start();
+    let digits = t.bytes().take_while(|b| b.is_ascii_digit()).count();
+    if digits == 0 || !t[digits..].starts_with(':') {
+        return line;
+    }
+    let rest = t[digits + 1..].trim_start();
+    let raw = rest
+        .bytes()
+        .take_while(|b| b.is_ascii_digit() || *b == b',' || *b == b'-')
+        .count();
+    &rest[raw..]
+}
+
+// Split on commas outside of quotes
+fn split_args(s: &str) -> Vec<&str> {
+    let mut args = vec![];
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == ',' => {
+                args.push(s[start..i].trim());
+                start = i + 1;
+            }
+            None => {}
+        }
+    }
+    let last = s[start..].trim();
+    if !last.is_empty() || !args.is_empty() {
+        args.push(last);
+    }
+    args
+}
+
+fn unescape(s: &str, line: usize) -> Result<Vec<i64>, ErrorAsm> {
+    let mut out = vec![];
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some(c @ '\\') | Some(c @ '"') | Some(c @ '\'') => c,
+                Some(c) => return err(line, format!("Unknown escape \\{}", c)),
+                None => return err(line, "Dangling \\"),
+            }
+        } else {
+            c
+        };
+        out.push(c as i64);
+    }
+    Ok(out)
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn parse_term(s: &str, line: usize) -> Result<Term, ErrorAsm> {
+    if s.len() >= 3 && s.starts_with('\'') && s.ends_with('\'') {
+        let v = unescape(&s[1..s.len() - 1], line)?;
+        if v.len() != 1 {
+            return err(line, format!("Bad character literal {}", s));
+        }
+        Ok(Term::Num(v[0]))
+    } else if let Ok(v) = s.parse() {
+        Ok(Term::Num(v))
+    } else if is_label(s) {
+        Ok(Term::Label(s.to_string()))
+    } else {
+        err(line, format!("Bad value `{}`", s))
+    }
+}
+
+// A negative literal keeps its sign, so `-9223372036854775808` parses
+fn parse_signed(sign: i64, s: &str, line: usize) -> Result<(i64, Term), ErrorAsm> {
+    if sign < 0 {
+        if let Ok(v) = format!("-{}", s).parse() {
+            return Ok((1, Term::Num(v)));
+        }
+    }
+    Ok((sign, parse_term(s, line)?))
+}
+
+fn parse_expr(s: &str, line: usize) -> Result<Expr, ErrorAsm> {
+    let s = s.trim();
+    if s.is_empty() {
+        return err(line, "Missing value");
+    }
+
+    let mut expr = vec![];
+    let mut sign = 1;
+    let mut cur = String::new();
+    let mut quote = false;
+    let mut escaped = false;
+    for c in s.chars() {
+        if quote {
+            cur.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '\'' {
+                quote = false;
+            }
+        } else if c == '\'' {
+            cur.push(c);
+            quote = true;
+        } else if c == '+' || c == '-' {
+            if !cur.trim().is_empty() {
+                expr.push(parse_signed(sign, cur.trim(), line)?);
+                cur.clear();
+                sign = 1;
+            }
+            if c == '-' {
+                sign = -sign;
+            }
+        } else {
+            cur.push(c);
+        }
+    }
+    expr.push(parse_signed(sign, cur.trim(), line)?);
+    Ok(expr)
+}
+
+fn parse_operand(s: &str, line: usize) -> Result<(Mode, Expr), ErrorAsm> {
+    if let Some(v) = s.strip_prefix('#') {
+        Ok((Mode::Im, parse_expr(v, line)?))
+    } else if s.starts_with('[') && s.ends_with(']') {
+        Ok((Mode::Pos, parse_expr(&s[1..s.len() - 1], line)?))
+    } else if let Some(v) = s.strip_prefix("rb") {
+        let v = v.trim();
+        if v.is_empty() {
+            Ok((Mode::Rel, vec![(1, Term::Num(0))]))
+        } else if v.starts_with('+') || v.starts_with('-') {
+            Ok((Mode::Rel, parse_expr(v, line)?))
+        } else {
+            err(line, format!("Bad operand `{}`", s))
+        }
+    } else {
+        err(
+            line,
+            format!("Bad operand `{}`, expected #imm, [pos] or rb+off", s),
+        )
+    }
+}
+
+fn parse_data(args: &str, line: usize) -> Result<Vec<Expr>, ErrorAsm> {
+    let mut data = vec![];
+    for arg in split_args(args) {
+        if arg.len() >= 2 && arg.starts_with('"') && arg.ends_with('"') {
+            for v in unescape(&arg[1..arg.len() - 1], line)? {
+                data.push(vec![(1, Term::Num(v))]);
+            }
+        } else {
+            data.push(parse_expr(arg, line)?);
+        }
+    }
+    Ok(data)
+}
+
+fn size(item: &Item) -> usize {
+    match item {
+        Item::Op(op, _) => op.size(),
+        Item::Data(d) => d.len(),
+    }
+}
+
+fn eval(expr: &Expr, labels: &HashMap<String, usize>, line: usize) -> Result<i64, ErrorAsm> {
+    let mut v: i64 = 0;
+    for (sign, term) in expr {
+        let t = match term {
+            Term::Num(n) => *n,
+            Term::Label(l) => match labels.get(l) {
+                Some(&a) => a as i64,
+                None => return err(line, format!("Unknown label `{}`", l)),
+            },
+        };
+        v = match t.checked_mul(*sign).and_then(|t| v.checked_add(t)) {
+            Some(v) => v,
+            None => return err(line, "Value out of range"),
+        };
+    }
+    Ok(v)
+}
+
+/// Assemble source text into program words
+pub fn assemble(src: &str) -> Result<Vec<i64>, ErrorAsm> {
+    let mut items: Vec<(usize, Item)> = vec![];
+    let mut labels = HashMap::new();
+    let mut addr = 0;
+
+    for (idx, raw) in src.lines().enumerate() {
+        let line = idx + 1;
+        let mut text = strip_comment(strip_listing(raw)).trim();
+
+        // Any number of labels in front
+        while let Some(colon) = text.find(':') {
+            let name = text[..colon].trim();
+            if !is_label(name) {
+                break;
+            }
+            if labels.insert(name.to_string(), addr).is_some() {
+                return err(line, format!("Duplicate label `{}`", name));
+            }
+            text = text[colon + 1..].trim();
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let (name, args) = match text.find(char::is_whitespace) {
+            Some(i) => (&text[..i], text[i..].trim()),
+            None => (text, ""),
+        };
+
+        let item = if name.eq_ignore_ascii_case(".data") || name.eq_ignore_ascii_case("DATA") {
+            Item::Data(parse_data(args, line)?)
+        } else {
+            let mut modes = vec![];
+            let mut exprs = vec![];
+            for arg in split_args(args) {
+                let (m, e) = parse_operand(arg, line)?;
+                modes.push(m);
+                exprs.push(e);
+            }
+            match mnemonic_op(name, &modes) {
+                Some(op) => Item::Op(op, exprs),
+                None => {
+                    return err(
+                        line,
+                        format!(
+                            "Unknown instruction `{}` with {} operands",
+                            name,
+                            modes.len()
+                        ),
+                    )
+                }
+            }
+        };
+
+        addr += size(&item);
+        items.push((line, item));
+    }
+
+    let mut mem = Vec::with_capacity(addr);
+    for (line, item) in items {
+        match item {
+            Item::Op(op, args) => {
+                mem.push(op.encode());
+                for a in args {
+                    mem.push(eval(&a, &labels, line)?);
+                }
+            }
+            Item::Data(data) => {
+                for d in data {
+                    mem.push(eval(&d, &labels, line)?);
+                }
+            }
+        }
+    }
+
+    Ok(mem)
+}
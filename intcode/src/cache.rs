@@ -0,0 +1,157 @@
+use crate::cpu::{to_addr, Fault, Step};
+use crate::{Cpu, Intcode, Mode};
+
+// The cached engine keeps a decoded instruction per address with its operand
+// words already resolved to an accessor. Memory tracks writes for it, and any
+// write that lands inside a cached instruction throws that entry away, so
+// self-modifying programs behave exactly as they do under the interpreter.
+
+// Longest instruction, a write to `a` can touch ones starting at `a - 3`
+const MAX_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Arg {
+    Im(i64),
+    Pos(i64),
+    Rel(i64),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Decoded {
+    op: Intcode,
+    args: [Arg; 3],
+}
+
+impl Cpu {
+    fn rel_addr(&self, off: i64) -> Result<usize, Fault> {
+        to_addr(off.checked_add(self.rel).ok_or(Fault::Overflow)?)
+    }
+
+    fn arg_get(&self, a: Arg) -> Result<i64, Fault> {
+        match a {
+            Arg::Im(v) => Ok(v),
+            Arg::Pos(p) => Ok(self.mem.get(to_addr(p)?)),
+            Arg::Rel(off) => Ok(self.mem.get(self.rel_addr(off)?)),
+        }
+    }
+
+    fn arg_set(&mut self, a: Arg, v: i64) -> Result<(), Fault> {
+        let addr = match a {
+            Arg::Im(_) => return Err(Fault::WriteToImmediate),
+            Arg::Pos(p) => to_addr(p)?,
+            Arg::Rel(off) => self.rel_addr(off)?,
+        };
+        Ok(self.mem.set(addr, v)?)
+    }
+
+    fn fetch(&mut self) -> Result<Decoded, Fault> {
+        // Memory that isn't tracking writes was swapped in from outside, so
+        // nothing cached can be trusted
+        if !self.mem.tracking_writes() {
+            self.cache.clear();
+            self.mem.track_writes(true);
+        }
+        let cache = &mut self.cache;
+        self.mem.drain_writes(|a| {
+            let end = cache.len().min(a + 1);
+            let from = end.min(a.saturating_sub(MAX_SIZE - 1));
+            for entry in &mut cache[from..end] {
+                *entry = None;
+            }
+        });
+
+        if let Some(Some(d)) = self.cache.get(self.pc) {
+            return Ok(*d);
+        }
+
+        let op = self.decode()?;
+        let mut args = [Arg::Im(0); 3];
+        for (i, m) in op.modes().into_iter().enumerate() {
+            let w = self.mem.get(self.pc + i + 1);
+            args[i] = match m {
+                Mode::Pos => Arg::Pos(w),
+                Mode::Im => Arg::Im(w),
+                Mode::Rel => Arg::Rel(w),
+            };
+        }
+        let d = Decoded { op, args };
+
+        // Only the contiguous part is cached, code doesn't live in the sparse
+        // far reaches of memory
+        if self.pc < self.mem.len() {
+            if self.cache.len() < self.mem.len() {
+                self.cache.resize(self.mem.len(), None);
+            }
+            self.cache[self.pc] = Some(d);
+        }
+        Ok(d)
+    }
+
+    pub(crate) fn exec_cached(&mut self, input: &mut Option<i64>) -> Result<Step, Fault> {
+        let Decoded {
+            op,
+            args: [a, b, c],
+        } = self.fetch()?;
+        match op {
+            Intcode::Add(..) => {
+                let v = self.arg_get(a)?.checked_add(self.arg_get(b)?);
+                self.arg_set(c, v.ok_or(Fault::Overflow)?)?;
+                self.pc += 4;
+            }
+            Intcode::Mult(..) => {
+                let v = self.arg_get(a)?.checked_mul(self.arg_get(b)?);
+                self.arg_set(c, v.ok_or(Fault::Overflow)?)?;
+                self.pc += 4;
+            }
+            Intcode::In(_) => {
+                let v = match input.take() {
+                    Some(v) => v,
+                    None => return Ok(Step::NeedsInput),
+                };
+                if let Err(e) = self.arg_set(a, v) {
+                    *input = Some(v);
+                    return Err(e);
+                }
+                self.pc += 2;
+            }
+            Intcode::Out(_) => {
+                let v = self.arg_get(a)?;
+                self.pc += 2;
+                return Ok(Step::Output(v));
+            }
+            Intcode::Jit(..) => {
+                let (p_a, p_b) = (self.arg_get(a)?, self.arg_get(b)?);
+                if p_a != 0 {
+                    self.pc = to_addr(p_b)?;
+                } else {
+                    self.pc += 3;
+                }
+            }
+            Intcode::Jif(..) => {
+                let (p_a, p_b) = (self.arg_get(a)?, self.arg_get(b)?);
+                if p_a == 0 {
+                    self.pc = to_addr(p_b)?;
+                } else {
+                    self.pc += 3;
+                }
+            }
+            Intcode::Lt(..) => {
+                let v = self.arg_get(a)? < self.arg_get(b)?;
+                self.arg_set(c, v as i64)?;
+                self.pc += 4;
+            }
+            Intcode::Equ(..) => {
+                let v = self.arg_get(a)? == self.arg_get(b)?;
+                self.arg_set(c, v as i64)?;
+                self.pc += 4;
+            }
+            Intcode::Adj(_) => {
+                let v = self.arg_get(a)?;
+                self.rel = self.rel.checked_add(v).ok_or(Fault::Overflow)?;
+                self.pc += 2;
+            }
+            Intcode::Halt => return Ok(Step::Halted),
+        }
+        Ok(Step::Continue)
+    }
+}
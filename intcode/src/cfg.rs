@@ -0,0 +1,329 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+
+use crate::disasm::Line;
+use crate::transpile::Flow;
+use crate::{Intcode, Mode};
+
+// Basic blocks and functions on top of the code `Flow` recovers. Compiled
+// Intcode calls a function by storing the return address at `rb+0` and
+// jumping to an immediate address. The function opens its frame with
+// `ARB #n`, drops it with `ARB #-n` and returns with a jump through `rb`.
+// So an unconditional immediate jump from a block that pushes a code address
+// is a call, its target a function entry, and a jump through `rb` a return.
+
+/// How control leaves a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    // Runs into the next block
+    Fall(usize),
+    Jump(usize),
+    Branch { taken: usize, fall: usize },
+    Call { target: usize, ret: usize },
+    CallIndirect(usize),
+    // Jump through `rb`, conditional ones can also fall through
+    Return(Option<usize>),
+    // Jump through a fixed address
+    Indirect(Option<usize>),
+    Halt,
+    // Runs off the end of the recovered code
+    End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Fall,
+    Taken,
+    Call,
+    // From a call site to where the call returns
+    Ret,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    pub ops: Vec<(usize, Intcode)>,
+    pub exit: Exit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub entry: usize,
+    // Size of the frame opened by an `ARB` in the entry block
+    pub frame: Option<i64>,
+    pub blocks: BTreeSet<usize>,
+    // Entries of called functions, tail jumps included
+    pub calls: BTreeSet<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Cfg {
+    pub blocks: BTreeMap<usize, Block>,
+    pub functions: BTreeMap<usize, Function>,
+}
+
+// Value stored by `ADD #v, #0, rb+n` and friends
+fn pushed(mem: &[i64], pc: usize, op: Intcode) -> Option<i64> {
+    match (op, &mem[pc + 1..pc + 3]) {
+        (Intcode::Add(Mode::Im, Mode::Im, Mode::Rel), &[a, 0])
+        | (Intcode::Add(Mode::Im, Mode::Im, Mode::Rel), &[0, a])
+        | (Intcode::Mult(Mode::Im, Mode::Im, Mode::Rel), &[a, 1])
+        | (Intcode::Mult(Mode::Im, Mode::Im, Mode::Rel), &[1, a]) => Some(a),
+        _ => None,
+    }
+}
+
+fn is_jump(op: Intcode) -> bool {
+    matches!(op, Intcode::Jit(..) | Intcode::Jif(..) | Intcode::Halt)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn exit(mem: &[i64], flow: &Flow, ops: &[(usize, Intcode)]) -> Exit {
+    let (pc, op) = ops[ops.len() - 1];
+    let next = pc + op.size();
+    let fall = if flow.code.contains_key(&next) {
+        Some(next)
+    } else {
+        None
+    };
+
+    // Immediate conditions make a jump either always or never taken
+    let (always, target) = match op {
+        Intcode::Jit(c, t) => (mem[pc + 1] != 0 && c == Mode::Im, t),
+        Intcode::Jif(c, t) => (mem[pc + 1] == 0 && c == Mode::Im, t),
+        Intcode::Halt => return Exit::Halt,
+        _ => return fall.map(Exit::Fall).unwrap_or(Exit::End),
+    };
+    let fall = if always { None } else { fall };
+    let ret = ops
+        .iter()
+        .rev()
+        .filter_map(|&(pc, op)| pushed(mem, pc, op))
+        .filter_map(|a| usize::try_from(a).ok())
+        .find(|a| flow.code.contains_key(a));
+
+    match (target, fall, ret) {
+        // Calls through a pointer look like returns, except for the push
+        (Mode::Rel, None, Some(ret)) | (Mode::Pos, None, Some(ret)) => Exit::CallIndirect(ret),
+        (Mode::Rel, ..) => Exit::Return(fall),
+        (Mode::Pos, ..) => Exit::Indirect(fall),
+        (Mode::Im, ..) => {
+            let target = match usize::try_from(mem[pc + 2]) {
+                Ok(a) => a,
+                Err(_) => return fall.map(Exit::Fall).unwrap_or(Exit::End),
+            };
+            match (fall, ret) {
+                (None, Some(ret)) => Exit::Call { target, ret },
+                (None, None) => Exit::Jump(target),
+                (Some(fall), _) => Exit::Branch {
+                    taken: target,
+                    fall,
+                },
+            }
+        }
+    }
+}
+
+impl Block {
+    /// Address just past the last instruction
+    pub fn end(&self) -> usize {
+        match self.ops.last() {
+            Some(&(pc, op)) => pc + op.size(),
+            None => self.start,
+        }
+    }
+
+    pub fn successors(&self) -> Vec<(usize, Edge)> {
+        match self.exit {
+            Exit::Fall(a) => vec![(a, Edge::Fall)],
+            Exit::Jump(a) => vec![(a, Edge::Taken)],
+            Exit::Branch { taken, fall } => vec![(taken, Edge::Taken), (fall, Edge::Fall)],
+            Exit::Call { target, ret } => vec![(target, Edge::Call), (ret, Edge::Ret)],
+            Exit::CallIndirect(ret) => vec![(ret, Edge::Ret)],
+            Exit::Return(fall) | Exit::Indirect(fall) => {
+                fall.into_iter().map(|a| (a, Edge::Fall)).collect()
+            }
+            Exit::Halt | Exit::End => vec![],
+        }
+    }
+
+    fn label(&self, mem: &[i64]) -> String {
+        let mut label = String::new();
+        for &(pc, op) in &self.ops {
+            let line = Line {
+                addr: pc,
+                words: mem[pc..pc + op.size()].to_vec(),
+                op: Some(op),
+            };
+            label.push_str(&escape(&format!("{:>5}: {}", pc, line.text())));
+            label.push_str("\\l");
+        }
+        label
+    }
+}
+
+impl Cfg {
+    /// Recover blocks and functions from a program, starting at address 0
+    pub fn recover(mem: &[i64]) -> Cfg {
+        let flow = Flow::recover(mem);
+
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        for (&pc, &op) in &flow.code {
+            if let Intcode::Jit(_, Mode::Im) | Intcode::Jif(_, Mode::Im) = op {
+                if let Ok(a) = usize::try_from(mem[pc + 2]) {
+                    leaders.insert(a);
+                }
+            }
+            if is_jump(op) {
+                leaders.insert(pc + op.size());
+            }
+        }
+
+        let mut cfg = Cfg::default();
+        let mut ops = flow.code.iter().map(|(&pc, &op)| (pc, op)).peekable();
+        while let Some((start, op)) = ops.next() {
+            let mut block = vec![(start, op)];
+            let mut next = start + op.size();
+            while !is_jump(block[block.len() - 1].1) && !leaders.contains(&next) {
+                match ops.peek() {
+                    Some(&(pc, op)) if pc == next => {
+                        block.push((pc, op));
+                        next += op.size();
+                        ops.next();
+                    }
+                    _ => break,
+                }
+            }
+            let exit = exit(mem, &flow, &block);
+            cfg.blocks.insert(
+                start,
+                Block {
+                    start,
+                    ops: block,
+                    exit,
+                },
+            );
+        }
+
+        cfg.find_functions(mem);
+        cfg
+    }
+
+    fn find_functions(&mut self, mem: &[i64]) {
+        let mut entries: BTreeSet<usize> = self
+            .blocks
+            .values()
+            .filter_map(|b| match b.exit {
+                Exit::Call { target, .. } => Some(target),
+                _ => None,
+            })
+            .filter(|a| self.blocks.contains_key(a))
+            .collect();
+        entries.insert(0);
+
+        for &entry in &entries {
+            let mut f = Function {
+                entry,
+                frame: None,
+                blocks: BTreeSet::new(),
+                calls: BTreeSet::new(),
+            };
+            if let Some(b) = self.blocks.get(&entry) {
+                f.frame = b.ops.iter().find_map(|&(pc, op)| match op {
+                    Intcode::Adj(Mode::Im) if mem[pc + 1] > 0 => Some(mem[pc + 1]),
+                    _ => None,
+                });
+            }
+
+            let mut todo = vec![entry];
+            while let Some(a) = todo.pop() {
+                let block = match self.blocks.get(&a) {
+                    Some(b) if f.blocks.insert(a) => b,
+                    _ => continue,
+                };
+                for (to, edge) in block.successors() {
+                    match edge {
+                        // Jumping to the top of another function is a tail call
+                        _ if edge == Edge::Call || to != entry && entries.contains(&to) => {
+                            f.calls.insert(to);
+                        }
+                        _ => todo.push(to),
+                    }
+                }
+            }
+            self.functions.insert(entry, f);
+        }
+    }
+
+    /// The function whose body includes the block starting at `addr`
+    pub fn function_of(&self, addr: usize) -> Option<&Function> {
+        self.functions.values().find(|f| f.blocks.contains(&addr))
+    }
+
+    /// Graphviz source for the whole graph, blocks grouped by function
+    pub fn to_dot(&self, mem: &[i64]) -> String {
+        let mut out = String::from("digraph cfg {\n");
+        out.push_str("    node [shape=box fontname=monospace];\n");
+
+        // Blocks shared between functions go with the first one
+        let mut placed = BTreeSet::new();
+        for f in self.functions.values() {
+            out.push_str(&format!("    subgraph cluster_{} {{\n", f.entry));
+            out.push_str(&format!("        label=\"fn @{}\";\n", f.entry));
+            for a in &f.blocks {
+                if placed.insert(*a) {
+                    let label = self.blocks[a].label(mem);
+                    out.push_str(&format!("        b{} [label=\"{}\"];\n", a, label));
+                }
+            }
+            out.push_str("    }\n");
+        }
+        for (a, b) in &self.blocks {
+            if !placed.contains(a) {
+                out.push_str(&format!("    b{} [label=\"{}\"];\n", a, b.label(mem)));
+            }
+        }
+
+        for (a, b) in &self.blocks {
+            for (to, edge) in b.successors() {
+                if !self.blocks.contains_key(&to) {
+                    continue;
+                }
+                let style = match edge {
+                    Edge::Fall => "",
+                    Edge::Taken => " [color=blue]",
+                    Edge::Call => " [style=bold color=red]",
+                    Edge::Ret => " [style=dashed]",
+                };
+                out.push_str(&format!("    b{} -> b{}{};\n", a, to, style));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Graphviz source for just the calls between functions
+    pub fn call_graph_dot(&self) -> String {
+        let mut out = String::from("digraph calls {\n");
+        out.push_str("    node [shape=box fontname=monospace];\n");
+        for f in self.functions.values() {
+            let frame = match f.frame {
+                Some(n) => format!("\\nframe {}", n),
+                None => String::new(),
+            };
+            let label = format!("fn @{}\\n{} blocks{}", f.entry, f.blocks.len(), frame);
+            out.push_str(&format!("    f{} [label=\"{}\"];\n", f.entry, label));
+        }
+        for f in self.functions.values() {
+            for to in &f.calls {
+                out.push_str(&format!("    f{} -> f{};\n", f.entry, to));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
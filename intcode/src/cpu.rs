@@ -0,0 +1,493 @@
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+use crate::cache::Decoded;
+use crate::mem::{ErrorMemory, Memory};
+use crate::profile::Profile;
+use crate::taint::Taint;
+use crate::trace::Event;
+use crate::{read_mem, ErrorIntcode, Intcode, Mode};
+
+/// Why the cpu refused to execute an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    InvalidOpcode(i64),
+    InvalidMode(i64),
+    WriteToImmediate,
+    NegativeAddress(i64),
+    Overflow,
+    OverLimit(usize),
+}
+
+impl From<ErrorMemory> for Fault {
+    fn from(e: ErrorMemory) -> Fault {
+        match e {
+            ErrorMemory::OverLimit(addr) => Fault::OverLimit(addr),
+        }
+    }
+}
+
+/// What happened after executing a single instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Continue,
+    Output(i64),
+    // Hit an `In` with nothing to feed it, pc is left on the instruction
+    NeedsInput,
+    Halted,
+}
+
+/// Budgets for `Cpu::run`, `None` is no limit. They're checked between
+/// instructions, counting from when they were set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub instructions: Option<u64>,
+    pub outputs: Option<u64>,
+    // Cells backed by storage, see `Memory::footprint`
+    pub memory: Option<usize>,
+    pub time: Option<Duration>,
+}
+
+/// Which of the `Limits` ran out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Instructions,
+    Outputs,
+    Memory,
+    Time,
+}
+
+// Reading the clock every instruction would cost more than the instruction
+const CLOCK_EVERY: u64 = 1024;
+
+#[derive(Debug, Clone, Copy)]
+struct Watchdog {
+    limits: Limits,
+    executed: u64,
+    outputs: u64,
+    started: Instant,
+}
+
+/// Why `Cpu::run` returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Halted,
+    NeedsInput,
+    Output(i64),
+    // The instruction at `pc` could not execute, nothing was changed
+    Fault { pc: usize, kind: Fault },
+    // Stopped before the instruction at `pc`, it can be resumed with new limits
+    LimitExceeded { pc: usize, limit: Limit },
+}
+
+impl Status {
+    /// `Some` for an output, `None` when halted or waiting for input.
+    /// Panics on a fault or running out of a limit, for harnesses that can't
+    /// do anything about one.
+    pub fn output(self) -> Option<i64> {
+        match self {
+            Status::Output(v) => Some(v),
+            Status::Halted | Status::NeedsInput => None,
+            Status::Fault { pc, kind } => panic!("Fault at {}: {:?}", pc, kind),
+            Status::LimitExceeded { pc, limit } => panic!("{:?} limit hit at {}", limit, pc),
+        }
+    }
+}
+
+pub(crate) fn to_addr(v: i64) -> Result<usize, Fault> {
+    if v < 0 {
+        Err(Fault::NegativeAddress(v))
+    } else {
+        Ok(v as usize)
+    }
+}
+
+// Address the parameter at `addr` refers to, `None` for immediates
+fn mem_addr(mem: &Memory, rel: i64, addr: usize, m: Mode) -> Result<Option<usize>, Fault> {
+    match m {
+        Mode::Pos => Ok(Some(to_addr(mem.get(addr))?)),
+        Mode::Im => Ok(None),
+        Mode::Rel => {
+            let a = mem.get(addr).checked_add(rel).ok_or(Fault::Overflow)?;
+            Ok(Some(to_addr(a)?))
+        }
+    }
+}
+
+fn mem_get(mem: &Memory, rel: i64, addr: usize, m: Mode) -> Result<i64, Fault> {
+    match mem_addr(mem, rel, addr, m)? {
+        Some(a) => Ok(mem.get(a)),
+        None => Ok(mem.get(addr)),
+    }
+}
+
+fn mem_set(mem: &mut Memory, rel: i64, addr: usize, m: Mode, v: i64) -> Result<(), Fault> {
+    match mem_addr(mem, rel, addr, m)? {
+        Some(a) => Ok(mem.set(a, v)?),
+        None => Err(Fault::WriteToImmediate),
+    }
+}
+
+/// How a `Cpu` executes instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    // Decode every instruction each time it's reached
+    Interp,
+    // Decode each address once and reuse it until something writes to the
+    // instruction
+    Cached,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cpu {
+    pub mem: Memory,
+    pub pc: usize,
+    pub rel: i64,
+    // Every executed instruction is appended here while it's `Some`
+    pub trace: Option<Vec<Event>>,
+    // Execution counts are gathered here while it's `Some`
+    pub profile: Option<Profile>,
+    // Data flow from tagged cells and inputs is tracked here while it's
+    // `Some`
+    pub taint: Option<Taint>,
+    // Read by `run_io` for an `In` that didn't get to run, it goes first next
+    // time
+    pub(crate) pending: Option<i64>,
+    engine: Engine,
+    pub(crate) cache: Vec<Option<Decoded>>,
+    watchdog: Option<Watchdog>,
+}
+
+// Offset and mode of the parameter an instruction writes to
+fn write_param(op: Intcode) -> Option<(usize, Mode)> {
+    match op {
+        Intcode::Add(_, _, m3)
+        | Intcode::Mult(_, _, m3)
+        | Intcode::Lt(_, _, m3)
+        | Intcode::Equ(_, _, m3) => Some((3, m3)),
+        Intcode::In(m1) => Some((1, m1)),
+        _ => None,
+    }
+}
+
+#[allow(clippy::new_without_default)]
+impl Cpu {
+    /// Load the program from `input`
+    pub fn new() -> Cpu {
+        Cpu::with_mem(read_mem())
+    }
+
+    pub fn with_mem(mem: Vec<i64>) -> Cpu {
+        Cpu::with_engine(mem, Engine::Interp)
+    }
+
+    pub fn with_engine(mem: Vec<i64>, engine: Engine) -> Cpu {
+        Cpu {
+            mem: mem.into(),
+            pc: 0,
+            rel: 0,
+            trace: None,
+            profile: None,
+            taint: None,
+            pending: None,
+            engine,
+            cache: vec![],
+            watchdog: None,
+        }
+    }
+
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    /// Decode the instruction at `pc`
+    pub fn decode(&self) -> Result<Intcode, Fault> {
+        let word = self.mem.get(self.pc);
+        Intcode::try_from(word).map_err(|e| match e {
+            ErrorIntcode::InvalidOpcode => Fault::InvalidOpcode(word),
+            ErrorIntcode::InvalidMode => Fault::InvalidMode(word),
+        })
+    }
+
+    /// Address the instruction at `pc` would write to, if any
+    pub fn write_target(&self) -> Option<usize> {
+        let (n, m) = write_param(self.decode().ok()?)?;
+        self.param_addr(n, m)
+    }
+
+    // Address parameter `n` of the instruction at `pc` refers to
+    pub(crate) fn param_addr(&self, n: usize, m: Mode) -> Option<usize> {
+        mem_addr(&self.mem, self.rel, self.pc + n, m).ok()?
+    }
+
+    /// Operand values of the instruction at `pc`, the address for the one
+    /// it writes to
+    pub fn operands(&self, op: Intcode) -> Result<Vec<i64>, Fault> {
+        let write = write_param(op).map(|(n, _)| n);
+        let mut args = vec![];
+        for (i, m) in op.modes().into_iter().enumerate() {
+            let addr = self.pc + i + 1;
+            let v = match mem_addr(&self.mem, self.rel, addr, m)? {
+                Some(a) if write == Some(i + 1) => a as i64,
+                Some(a) => self.mem.get(a),
+                None => self.mem.get(addr),
+            };
+            args.push(v);
+        }
+        Ok(args)
+    }
+
+    /// Bound what `run` may do from here on, counters and the clock start
+    /// from zero
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.watchdog = Some(Watchdog {
+            limits,
+            executed: 0,
+            outputs: 0,
+            started: Instant::now(),
+        });
+    }
+
+    pub fn clear_limits(&mut self) {
+        self.watchdog = None;
+    }
+
+    pub fn limits(&self) -> Option<Limits> {
+        self.watchdog.map(|w| w.limits)
+    }
+
+    fn over_limit(&self) -> Option<Limit> {
+        let w = self.watchdog.as_ref()?;
+        let l = &w.limits;
+        if l.instructions.is_some_and(|n| w.executed >= n) {
+            return Some(Limit::Instructions);
+        }
+        if l.memory.is_some_and(|n| self.mem.footprint() > n) {
+            return Some(Limit::Memory);
+        }
+        // Only the output that would go over is stopped
+        if l.outputs.is_some_and(|n| w.outputs >= n) {
+            if let Ok(Intcode::Out(_)) = self.decode() {
+                return Some(Limit::Outputs);
+            }
+        }
+        if let Some(t) = l.time {
+            if w.executed % CLOCK_EVERY == 0 && w.started.elapsed() >= t {
+                return Some(Limit::Time);
+            }
+        }
+        None
+    }
+
+    fn count(&mut self, output: bool) {
+        if let Some(w) = self.watchdog.as_mut() {
+            w.executed += 1;
+            w.outputs += output as u64;
+        }
+    }
+
+    /// Start recording executed instructions, dropping any earlier trace
+    pub fn start_trace(&mut self) {
+        self.trace = Some(vec![]);
+    }
+
+    /// Stop recording and hand back what was recorded
+    pub fn take_trace(&mut self) -> Vec<Event> {
+        self.trace.take().unwrap_or_default()
+    }
+
+    /// Start counting executed instructions, dropping any earlier profile
+    pub fn start_profile(&mut self) {
+        self.profile = Some(Profile::default());
+    }
+
+    /// Stop profiling and hand back the counts
+    pub fn take_profile(&mut self) -> Profile {
+        self.profile.take().unwrap_or_default()
+    }
+
+    /// Start tracking data flow with no tags, dropping any earlier tracking
+    pub fn start_taint(&mut self) {
+        self.taint = Some(Taint::default());
+    }
+
+    /// Stop tracking and hand back what was tracked
+    pub fn take_taint(&mut self) -> Taint {
+        self.taint.take().unwrap_or_default()
+    }
+
+    /// Execute one instruction. `input` is only consumed by `In`. On a fault
+    /// the cpu is left as it was.
+    pub fn step(&mut self, input: &mut Option<i64>) -> Result<Step, Fault> {
+        if self.trace.is_none() && self.profile.is_none() && self.taint.is_none() {
+            return self.exec(input);
+        }
+
+        let (pc, rel, fed) = (self.pc, self.rel, *input);
+        let op = self.decode()?;
+        // Same as `exec`, waiting for input comes before any operand faults
+        if let (Intcode::In(_), None) = (op, fed) {
+            return Ok(Step::NeedsInput);
+        }
+        let args = match self.trace {
+            Some(_) => self.operands(op)?,
+            None => vec![],
+        };
+        let params = match self.taint {
+            Some(ref taint) => taint.params(self, op),
+            None => vec![],
+        };
+        let target = self.write_target();
+
+        let step = self.exec(input)?;
+        if step == Step::NeedsInput {
+            return Ok(step);
+        }
+
+        if let Some(profile) = self.profile.as_mut() {
+            profile.record(pc, op, self.rel.cmp(&rel));
+        }
+        let write = target.map(|a| (a, self.mem.get(a)));
+        if let Some(taint) = self.taint.as_mut() {
+            taint.record(pc, op, &params, write, step);
+        }
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(Event {
+                pc,
+                op,
+                args,
+                write,
+                rel: Some(self.rel).filter(|&r| r != rel),
+                input: fed.filter(|_| input.is_none()),
+                output: match step {
+                    Step::Output(v) => Some(v),
+                    _ => None,
+                },
+            });
+        }
+        Ok(step)
+    }
+
+    fn exec(&mut self, input: &mut Option<i64>) -> Result<Step, Fault> {
+        match self.engine {
+            Engine::Interp => self.interp(input),
+            Engine::Cached => self.exec_cached(input),
+        }
+    }
+
+    fn interp(&mut self, input: &mut Option<i64>) -> Result<Step, Fault> {
+        let op = self.decode()?;
+        match op {
+            Intcode::Add(m1, m2, m3) => {
+                let (p_a, p_b) = (
+                    mem_get(&self.mem, self.rel, self.pc + 1, m1)?,
+                    mem_get(&self.mem, self.rel, self.pc + 2, m2)?,
+                );
+                let v = p_a.checked_add(p_b).ok_or(Fault::Overflow)?;
+                mem_set(&mut self.mem, self.rel, self.pc + 3, m3, v)?;
+                self.pc += 4;
+            }
+            Intcode::Mult(m1, m2, m3) => {
+                let (p_a, p_b) = (
+                    mem_get(&self.mem, self.rel, self.pc + 1, m1)?,
+                    mem_get(&self.mem, self.rel, self.pc + 2, m2)?,
+                );
+                let v = p_a.checked_mul(p_b).ok_or(Fault::Overflow)?;
+                mem_set(&mut self.mem, self.rel, self.pc + 3, m3, v)?;
+                self.pc += 4;
+            }
+            Intcode::In(m1) => {
+                let v = match input.take() {
+                    Some(v) => v,
+                    None => return Ok(Step::NeedsInput),
+                };
+                if let Err(e) = mem_set(&mut self.mem, self.rel, self.pc + 1, m1, v) {
+                    // Leave the input for whoever handles the fault
+                    *input = Some(v);
+                    return Err(e);
+                }
+                self.pc += 2;
+            }
+            Intcode::Out(m1) => {
+                let p_a = mem_get(&self.mem, self.rel, self.pc + 1, m1)?;
+                self.pc += 2;
+                return Ok(Step::Output(p_a));
+            }
+            Intcode::Jit(m1, m2) => {
+                let (p_a, p_b) = (
+                    mem_get(&self.mem, self.rel, self.pc + 1, m1)?,
+                    mem_get(&self.mem, self.rel, self.pc + 2, m2)?,
+                );
+                if p_a != 0 {
+                    self.pc = to_addr(p_b)?;
+                } else {
+                    self.pc += 3;
+                }
+            }
+            Intcode::Jif(m1, m2) => {
+                let (p_a, p_b) = (
+                    mem_get(&self.mem, self.rel, self.pc + 1, m1)?,
+                    mem_get(&self.mem, self.rel, self.pc + 2, m2)?,
+                );
+                if p_a == 0 {
+                    self.pc = to_addr(p_b)?;
+                } else {
+                    self.pc += 3;
+                }
+            }
+            Intcode::Lt(m1, m2, m3) => {
+                let (p_a, p_b) = (
+                    mem_get(&self.mem, self.rel, self.pc + 1, m1)?,
+                    mem_get(&self.mem, self.rel, self.pc + 2, m2)?,
+                );
+                mem_set(&mut self.mem, self.rel, self.pc + 3, m3, (p_a < p_b) as i64)?;
+                self.pc += 4;
+            }
+            Intcode::Equ(m1, m2, m3) => {
+                let (p_a, p_b) = (
+                    mem_get(&self.mem, self.rel, self.pc + 1, m1)?,
+                    mem_get(&self.mem, self.rel, self.pc + 2, m2)?,
+                );
+                mem_set(
+                    &mut self.mem,
+                    self.rel,
+                    self.pc + 3,
+                    m3,
+                    (p_a == p_b) as i64,
+                )?;
+                self.pc += 4;
+            }
+            Intcode::Adj(m1) => {
+                let p_a = mem_get(&self.mem, self.rel, self.pc + 1, m1)?;
+                self.rel = self.rel.checked_add(p_a).ok_or(Fault::Overflow)?;
+                self.pc += 2;
+            }
+            Intcode::Halt => {
+                return Ok(Step::Halted);
+            }
+        }
+        Ok(Step::Continue)
+    }
+
+    /// Run until the next output, halt, fault, an `In` while `input` is
+    /// empty, or running out of one of the limits
+    pub fn run(&mut self, input: &mut Option<i64>) -> Status {
+        loop {
+            if self.watchdog.is_some() {
+                if let Some(limit) = self.over_limit() {
+                    return Status::LimitExceeded { pc: self.pc, limit };
+                }
+            }
+            match self.step(input) {
+                Ok(Step::Continue) => self.count(false),
+                Ok(Step::Output(v)) => {
+                    self.count(true);
+                    return Status::Output(v);
+                }
+                Ok(Step::NeedsInput) => return Status::NeedsInput,
+                Ok(Step::Halted) => return Status::Halted,
+                Err(kind) => return Status::Fault { pc: self.pc, kind },
+            }
+        }
+    }
+}
@@ -0,0 +1,508 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use crate::disasm;
+use crate::snapshot::Snapshot;
+use crate::taint::{Flow, Mark, Sink, Taint};
+use crate::{Cpu, Fault, Step};
+
+const HELP: &str = "\
+s, step [n]         execute n instructions (default 1)
+rs, rstep [n]       step back n instructions (default 1)
+rw <addr>           step back to just before the last write to addr
+c, continue         run until a breakpoint, watchpoint, halt, fault or missing input
+b, break <addr>     stop when pc reaches addr
+db <addr>           delete a breakpoint
+w, watch <addr>     stop after an instruction writes addr
+dw <addr>           delete a watchpoint
+info                list breakpoints, watchpoints and queued input
+tag <addr> [name]   follow where the value in addr goes, named after addr by default
+tag in [name]       tag every input value read from here on
+tag data            give every cell outside the recovered code a tag named after it
+taint <addr>        show the tags the value in addr was computed from
+flows [n]           show the last n writes and outputs of tagged values (default 10)
+r, regs             show pc and rel
+x <addr> [n]        dump n memory cells (default 8)
+l, list [addr] [n]  disassemble n instructions (default from pc, 10)
+set <addr> <v>      write v to memory, `set pc <v>` and `set rel <v>` for registers
+in <v>...           queue input values
+text <line>         queue a line of ASCII input, newline included
+ascii [on|off]      show output as text
+out                 show all output so far
+save <file>         write a snapshot of the cpu, queued input and output
+load <file>         resume from a snapshot
+h, help             this
+q, quit             exit
+(empty line repeats the last step or continue)";
+
+// Instructions kept in the undo log, the oldest are dropped past this
+const HISTORY: usize = 1 << 20;
+
+// Most words `x` shows at once
+const DUMP: usize = 1 << 16;
+
+/// Why the debugger handed control back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    // Finished the requested number of steps
+    Stepped,
+    Breakpoint(usize),
+    Watchpoint { addr: usize, old: i64, new: i64 },
+    NeedsInput,
+    Halted,
+    Fault { pc: usize, kind: Fault },
+}
+
+// What it takes to undo one instruction
+#[derive(Debug, Clone)]
+struct Undo {
+    pc: usize,
+    rel: i64,
+    // Address written and the value it had before
+    write: Option<(usize, i64)>,
+    input: Option<i64>,
+    output: bool,
+    taint: Option<Mark>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Debugger {
+    pub cpu: Cpu,
+    pub breakpoints: BTreeSet<usize>,
+    pub watchpoints: BTreeSet<usize>,
+    pub input: VecDeque<i64>,
+    pub output: Vec<i64>,
+    pub ascii: bool,
+    repeat: Option<String>,
+    history: VecDeque<Undo>,
+}
+
+fn flow_text(taint: &Taint, f: &Flow) -> String {
+    let to = match f.to {
+        Sink::Mem(a) => format!("[{}]", a),
+        Sink::Output(_) => "out".to_string(),
+    };
+    format!(
+        "{:>5}: {} = {} <- {}\n",
+        f.pc,
+        to,
+        f.value,
+        taint.describe(&f.labels)
+    )
+}
+
+fn parse_num<T: std::str::FromStr>(s: Option<&str>, what: &str) -> Result<T, String> {
+    let s = s.ok_or_else(|| format!("Missing {}", what))?;
+    s.parse().map_err(|_| format!("Bad {} `{}`", what, s))
+}
+
+impl Debugger {
+    pub fn new(cpu: Cpu) -> Debugger {
+        Debugger {
+            cpu,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            input: VecDeque::new(),
+            output: vec![],
+            ascii: false,
+            repeat: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Execute a single instruction, feeding it from the input queue
+    pub fn step(&mut self) -> Option<Stop> {
+        let target = self.cpu.write_target();
+        let mut undo = Undo {
+            pc: self.cpu.pc,
+            rel: self.cpu.rel,
+            write: target.map(|a| (a, self.cpu.mem.get(a))),
+            input: None,
+            output: false,
+            taint: self.cpu.taint.as_ref().map(|t| t.mark(target)),
+        };
+        let watched = target.filter(|a| self.watchpoints.contains(a));
+        let old = undo.write.map(|(_, v)| v);
+
+        let mut feed = self.input.front().copied();
+        let fed = feed.is_some();
+        match self.cpu.step(&mut feed) {
+            Ok(Step::Continue) => {}
+            Ok(Step::Output(v)) => {
+                self.output.push(v);
+                undo.output = true;
+            }
+            Ok(Step::NeedsInput) => return Some(Stop::NeedsInput),
+            Ok(Step::Halted) => return Some(Stop::Halted),
+            Err(kind) => {
+                let pc = self.cpu.pc;
+                return Some(Stop::Fault { pc, kind });
+            }
+        }
+        if fed && feed.is_none() {
+            undo.input = self.input.pop_front();
+        }
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(undo);
+
+        if let (Some(addr), Some(old)) = (watched, old) {
+            let new = self.cpu.mem.get(addr);
+            return Some(Stop::Watchpoint { addr, old, new });
+        }
+        None
+    }
+
+    /// Step `n` times, stopping early for the same reasons as `cont`
+    pub fn step_n(&mut self, n: usize) -> Stop {
+        for _ in 0..n {
+            if let Some(s) = self.step() {
+                return s;
+            }
+            if self.breakpoints.contains(&self.cpu.pc) {
+                return Stop::Breakpoint(self.cpu.pc);
+            }
+        }
+        Stop::Stepped
+    }
+
+    pub fn cont(&mut self) -> Stop {
+        loop {
+            if let Some(s) = self.step() {
+                return s;
+            }
+            if self.breakpoints.contains(&self.cpu.pc) {
+                return Stop::Breakpoint(self.cpu.pc);
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            cpu: self.cpu.clone(),
+            input: self.input.iter().copied().collect(),
+            output: self.output.clone(),
+        }
+    }
+
+    /// Pick up from a snapshot, breakpoints and watchpoints are kept
+    pub fn restore(&mut self, snap: Snapshot) {
+        self.history.clear();
+        self.cpu = snap.cpu;
+        self.input = snap.input.into();
+        self.output = snap.output;
+    }
+
+    /// Undo the last executed instruction, false if there's no history left
+    pub fn step_back(&mut self) -> bool {
+        let undo = match self.history.pop_back() {
+            Some(u) => u,
+            None => return false,
+        };
+        self.cpu.pc = undo.pc;
+        self.cpu.rel = undo.rel;
+        if let Some((addr, old)) = undo.write {
+            self.cpu.mem[addr] = old;
+        }
+        if let Some(v) = undo.input {
+            self.input.push_front(v);
+        }
+        if undo.output {
+            self.output.pop();
+        }
+        if let (Some(taint), Some(mark)) = (self.cpu.taint.as_mut(), undo.taint) {
+            taint.revert(mark);
+        }
+        true
+    }
+
+    /// Step back up to `n` instructions, returns how many were undone
+    pub fn back_n(&mut self, n: usize) -> usize {
+        (0..n).take_while(|_| self.step_back()).count()
+    }
+
+    /// Step back until the last instruction that wrote `addr` is about to
+    /// execute again. Nothing changes if it isn't in the history.
+    pub fn back_to_write(&mut self, addr: usize) -> Option<usize> {
+        let pos = self
+            .history
+            .iter()
+            .rposition(|u| u.write.map(|(a, _)| a) == Some(addr))?;
+        Some(self.back_n(self.history.len() - pos))
+    }
+
+    fn show_output(&self, from: usize) -> String {
+        let out = &self.output[from..];
+        if self.ascii {
+            out.iter()
+                .map(|&v| match v {
+                    0..=127 => (v as u8 as char).to_string(),
+                    _ => format!("[{}]", v),
+                })
+                .collect()
+        } else {
+            let vals: Vec<String> = out.iter().map(|v| v.to_string()).collect();
+            format!("out: {}\n", vals.join(", "))
+        }
+    }
+
+    fn list(&self, addr: usize, count: usize) -> String {
+        let mut out = String::new();
+        for line in disasm::disassemble_from(self.cpu.mem.as_slice(), addr, count) {
+            let mark = if line.addr == self.cpu.pc { "=>" } else { "  " };
+            let bp = if self.breakpoints.contains(&line.addr) {
+                "*"
+            } else {
+                " "
+            };
+            out.push_str(&format!("{}{}{}\n", mark, bp, line));
+        }
+        out
+    }
+
+    fn report(&self, stop: Stop, out_from: usize) -> String {
+        let mut out = String::new();
+        if self.output.len() > out_from {
+            out.push_str(&self.show_output(out_from));
+            if !out.ends_with('\n') {
+                out.push('\n');
+            }
+        }
+        match stop {
+            Stop::Stepped => {}
+            Stop::Breakpoint(pc) => out.push_str(&format!("Breakpoint at {}\n", pc)),
+            Stop::Watchpoint { addr, old, new } => {
+                out.push_str(&format!("Watchpoint [{}]: {} -> {}\n", addr, old, new))
+            }
+            Stop::NeedsInput => out.push_str("Waiting for input\n"),
+            Stop::Halted => out.push_str("Halted\n"),
+            Stop::Fault { pc, kind } => out.push_str(&format!("Fault at {}: {:?}\n", pc, kind)),
+        }
+        out.push_str(&self.list(self.cpu.pc, 1));
+        out
+    }
+
+    fn exec(&mut self, line: &str) -> Result<String, String> {
+        let mut words = line.split_whitespace();
+        let cmd = match words.next() {
+            Some(c) => c,
+            None => return Ok(String::new()),
+        };
+
+        let out = match cmd {
+            "s" | "step" => {
+                let n = match words.next() {
+                    Some(n) => parse_num(Some(n), "count")?,
+                    None => 1,
+                };
+                let from = self.output.len();
+                let stop = self.step_n(n);
+                self.repeat = Some(line.to_string());
+                self.report(stop, from)
+            }
+            "rs" | "rstep" => {
+                let n = match words.next() {
+                    Some(n) => parse_num(Some(n), "count")?,
+                    None => 1,
+                };
+                let done = self.back_n(n);
+                self.repeat = Some(line.to_string());
+                if done < n {
+                    format!(
+                        "Stepped back {}, no more history\n{}",
+                        done,
+                        self.list(self.cpu.pc, 1)
+                    )
+                } else {
+                    self.list(self.cpu.pc, 1)
+                }
+            }
+            "rw" => {
+                let addr = parse_num(words.next(), "address")?;
+                match self.back_to_write(addr) {
+                    Some(n) => format!("Stepped back {}\n{}", n, self.list(self.cpu.pc, 1)),
+                    None => return Err(format!("No write to [{}] in the history", addr)),
+                }
+            }
+            "c" | "continue" => {
+                let from = self.output.len();
+                let stop = self.cont();
+                self.repeat = Some(line.to_string());
+                self.report(stop, from)
+            }
+            "b" | "break" => {
+                let addr = parse_num(words.next(), "address")?;
+                self.breakpoints.insert(addr);
+                format!("Breakpoint at {}\n", addr)
+            }
+            "db" => {
+                let addr = parse_num(words.next(), "address")?;
+                if !self.breakpoints.remove(&addr) {
+                    return Err(format!("No breakpoint at {}", addr));
+                }
+                String::new()
+            }
+            "w" | "watch" => {
+                let addr = parse_num(words.next(), "address")?;
+                self.watchpoints.insert(addr);
+                format!("Watching [{}] = {}\n", addr, self.cpu.mem.get(addr))
+            }
+            "dw" => {
+                let addr = parse_num(words.next(), "address")?;
+                if !self.watchpoints.remove(&addr) {
+                    return Err(format!("No watchpoint on {}", addr));
+                }
+                String::new()
+            }
+            "info" => format!(
+                "breakpoints: {:?}\nwatchpoints: {:?}\ninput: {:?}\n",
+                self.breakpoints, self.watchpoints, self.input
+            ),
+            "tag" => {
+                let what = words.next().ok_or("Missing address")?;
+                if self.cpu.taint.is_none() {
+                    self.cpu.start_taint();
+                }
+                let mem = self.cpu.mem.as_slice();
+                let taint = self.cpu.taint.as_mut().unwrap();
+                match what {
+                    "in" => {
+                        let tag = taint.new_tag(words.next().unwrap_or("in"));
+                        taint.input.insert(tag);
+                        "Tagging input\n".to_string()
+                    }
+                    "data" => {
+                        format!("Tagged {} cells\n", taint.tag_data(mem))
+                    }
+                    _ => {
+                        let addr = parse_num(Some(what), "address")?;
+                        let name = match words.next() {
+                            Some(n) => n.to_string(),
+                            None => format!("[{}]", addr),
+                        };
+                        let tag = taint.new_tag(&name);
+                        taint.tag_cell(addr, tag);
+                        format!("Tagged [{}] as {}\n", addr, name)
+                    }
+                }
+            }
+            "taint" => {
+                let addr = parse_num(words.next(), "address")?;
+                let taint = self.cpu.taint.as_ref().ok_or("Nothing tagged")?;
+                format!("[{}] <- {}\n", addr, taint.describe(&taint.labels(addr)))
+            }
+            "flows" => {
+                let n: usize = match words.next() {
+                    Some(n) => parse_num(Some(n), "count")?,
+                    None => 10,
+                };
+                let taint = self.cpu.taint.as_ref().ok_or("Nothing tagged")?;
+                let from = taint.flows.len().saturating_sub(n);
+                taint.flows[from..]
+                    .iter()
+                    .map(|f| flow_text(taint, f))
+                    .collect()
+            }
+            "r" | "regs" => format!("pc={} rel={}\n", self.cpu.pc, self.cpu.rel),
+            "x" => {
+                let addr: usize = parse_num(words.next(), "address")?;
+                let n = match words.next() {
+                    Some(n) => parse_num(Some(n), "count")?,
+                    None => 8,
+                };
+                if n > DUMP {
+                    return Err(format!("Count {} is over {}", n, DUMP));
+                }
+                let end = addr.checked_add(n).ok_or("Address out of range")?;
+                let mut out = String::new();
+                for row in (addr..end).step_by(8) {
+                    let vals: Vec<String> = (row..end.min(row.saturating_add(8)))
+                        .map(|a| format!("{:>8}", self.cpu.mem.get(a)))
+                        .collect();
+                    out.push_str(&format!("{:>5}:{}\n", row, vals.join("")));
+                }
+                out
+            }
+            "l" | "list" => {
+                let addr = match words.next() {
+                    Some(a) => parse_num(Some(a), "address")?,
+                    None => self.cpu.pc,
+                };
+                let n = match words.next() {
+                    Some(n) => parse_num(Some(n), "count")?,
+                    None => 10,
+                };
+                self.list(addr, n)
+            }
+            "set" => {
+                let what = words.next().ok_or("Missing address")?;
+                let v: i64 = parse_num(words.next(), "value")?;
+                match what {
+                    "pc" => {
+                        if v < 0 {
+                            return Err(format!("Bad pc {}", v));
+                        }
+                        self.cpu.pc = v as usize;
+                    }
+                    "rel" => self.cpu.rel = v,
+                    _ => {
+                        let addr = parse_num(Some(what), "address")?;
+                        self.cpu.mem.set(addr, v).map_err(|e| format!("{:?}", e))?;
+                    }
+                }
+                // Undoing across an edit would mix up two different runs
+                self.history.clear();
+                String::new()
+            }
+            "in" => {
+                let vals = words
+                    .map(|w| parse_num(Some(w), "value"))
+                    .collect::<Result<Vec<i64>, _>>()?;
+                self.input.extend(vals);
+                String::new()
+            }
+            "text" => {
+                let text = line.trim_start()[cmd.len()..].trim_start();
+                self.input.extend(text.chars().map(|c| c as i64));
+                self.input.push_back('\n' as i64);
+                String::new()
+            }
+            "ascii" => {
+                self.ascii = match words.next() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    None => !self.ascii,
+                    Some(w) => return Err(format!("Expected on or off, got `{}`", w)),
+                };
+                format!("ascii {}\n", if self.ascii { "on" } else { "off" })
+            }
+            "out" => self.show_output(0),
+            "save" => {
+                let path = words.next().ok_or("Missing file")?;
+                self.snapshot().save(path).map_err(|e| e.to_string())?;
+                format!("Saved to {}\n", path)
+            }
+            "load" => {
+                let path = words.next().ok_or("Missing file")?;
+                let snap = Snapshot::load(path).map_err(|e| format!("{}: {}", path, e))?;
+                self.restore(snap);
+                self.list(self.cpu.pc, 1)
+            }
+            "h" | "help" => format!("{}\n", HELP),
+            _ => return Err(format!("Unknown command `{}`, try help", cmd)),
+        };
+        Ok(out)
+    }
+
+    /// Run one line of debugger input and return what to print
+    pub fn command(&mut self, line: &str) -> String {
+        let line = match (line.trim(), &self.repeat) {
+            ("", Some(prev)) => prev.clone(),
+            (l, _) => l.to_string(),
+        };
+        match self.exec(&line) {
+            Ok(out) => out,
+            Err(e) => format!("{}\n", e),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::{Intcode, Mode};
+
+pub fn mnemonic(op: Intcode) -> &'static str {
+    match op {
+        Intcode::Add(..) => "ADD",
+        Intcode::Mult(..) => "MUL",
+        Intcode::In(_) => "IN",
+        Intcode::Out(_) => "OUT",
+        Intcode::Jit(..) => "JIT",
+        Intcode::Jif(..) => "JIF",
+        Intcode::Lt(..) => "LT",
+        Intcode::Equ(..) => "EQ",
+        Intcode::Adj(_) => "ARB",
+        Intcode::Halt => "HLT",
+    }
+}
+
+/// `[12]`, `#5` or `rb+3`
+pub fn operand(m: Mode, v: i64) -> String {
+    match m {
+        Mode::Pos => format!("[{}]", v),
+        Mode::Im => format!("#{}", v),
+        Mode::Rel if v < 0 => format!("rb-{}", v.unsigned_abs()),
+        Mode::Rel => format!("rb+{}", v),
+    }
+}
+
+/// One decoded instruction, or a single `DATA` word
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub addr: usize,
+    pub words: Vec<i64>,
+    pub op: Option<Intcode>,
+}
+
+impl Line {
+    /// Just the assembly part, without the address and raw words
+    pub fn text(&self) -> String {
+        match self.op {
+            Some(op) => {
+                let args: Vec<String> = op
+                    .modes()
+                    .into_iter()
+                    .zip(&self.words[1..])
+                    .map(|(m, &v)| operand(m, v))
+                    .collect();
+                if args.is_empty() {
+                    mnemonic(op).to_string()
+                } else {
+                    format!("{:<4}{}", mnemonic(op), args.join(", "))
+                }
+            }
+            None => format!("DATA {}", self.words[0]),
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let raw: Vec<String> = self.words.iter().map(|w| w.to_string()).collect();
+        write!(f, "{:>5}: {:<28} {}", self.addr, raw.join(","), self.text())
+    }
+}
+
+// Only take words that re-encode to themselves as instructions so the
+// listing assembles back to the exact same program
+fn decode_at(mem: &[i64], addr: usize) -> Option<Intcode> {
+    let op = Intcode::try_from(mem[addr]).ok()?;
+    if op.encode() != mem[addr] || addr + op.size() > mem.len() {
+        return None;
+    }
+    Some(op)
+}
+
+fn line_at(mem: &[i64], addr: usize) -> Line {
+    let op = decode_at(mem, addr);
+    let size = op.map(|op| op.size()).unwrap_or(1);
+    Line {
+        addr,
+        words: mem[addr..addr + size].to_vec(),
+        op,
+    }
+}
+
+/// Linear sweep over `mem` from address 0
+pub fn disassemble(mem: &[i64]) -> Vec<Line> {
+    disassemble_from(mem, 0, usize::MAX)
+}
+
+/// Up to `count` lines starting at `addr`
+pub fn disassemble_from(mem: &[i64], mut addr: usize, count: usize) -> Vec<Line> {
+    let mut lines = vec![];
+
+    while addr < mem.len() && lines.len() < count {
+        let line = line_at(mem, addr);
+        addr += line.words.len();
+        lines.push(line);
+    }
+
+    lines
+}
+
+pub fn listing(mem: &[i64]) -> String {
+    let mut out = String::new();
+    for line in disassemble(mem) {
+        out.push_str(&line.to_string());
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,328 @@
+use std::fmt;
+
+use crate::io::Iter;
+use crate::{Cpu, Engine, Limits, Status};
+
+// Differential testing: random programs and inputs run through the
+// interpreter and every other way of executing them, which all have to agree
+// on outputs, memory, registers and how the run ended. Programs are mostly
+// well formed instructions over a handful of addresses, so they read and
+// write their own code a lot, with the odd garbage word to get faults.
+//
+// The transpiler can't take part here since its output has to be compiled,
+// intcode-aot checks it against the day inputs instead.
+
+/// Where the generator gets its choices from
+pub trait Source {
+    fn next_u64(&mut self) -> u64;
+
+    /// Uniform in `0..n`, `n` must not be 0
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// Uniform in `lo..=hi`
+    fn range(&mut self, lo: i64, hi: i64) -> i64 {
+        lo + self.below((hi - lo + 1) as u64) as i64
+    }
+}
+
+/// splitmix64, seeded so CI runs are reproducible
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+}
+
+impl Source for Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Choices read from raw fuzzer bytes, zeros once they run out
+#[derive(Debug, Clone)]
+pub struct Bytes<'a>(pub &'a [u8]);
+
+impl<'a> Source for Bytes<'a> {
+    fn next_u64(&mut self) -> u64 {
+        let n = self.0.len().min(8);
+        let mut word = [0; 8];
+        word[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        u64::from_le_bytes(word)
+    }
+}
+
+// Instructions run before a case counts as stuck
+const STEPS: u64 = 10_000;
+
+/// A program and the input it gets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub prog: Vec<i64>,
+    pub input: Vec<i64>,
+}
+
+fn mode(src: &mut impl Source, write: bool) -> i64 {
+    match src.below(20) {
+        // Writing to an immediate faults, keep it rare
+        0 if write => 1,
+        n if write => [0, 2][n as usize % 2],
+        n => [0, 1, 2][n as usize % 3],
+    }
+}
+
+fn operand(src: &mut impl Source, mode: i64, len: i64) -> i64 {
+    match (mode, src.below(16)) {
+        (_, 0) => src.range(-1_000_000, 1_000_000),
+        (_, 1) => -src.range(1, 4),
+        (0, _) => src.range(0, len + 8),
+        (2, _) => src.range(-8, 8),
+        _ => src.range(-2, 20),
+    }
+}
+
+impl Case {
+    /// A program of roughly 8 to 64 words and up to 16 inputs
+    pub fn generate(src: &mut impl Source) -> Case {
+        let len = src.range(8, 64);
+        let mut prog = vec![];
+        while (prog.len() as i64) < len {
+            let (op, params) = match src.below(100) {
+                0..=7 => (1, 3),
+                8..=15 => (2, 3),
+                16..=21 => (7, 3),
+                22..=27 => (8, 3),
+                28..=37 => (3, 1),
+                38..=51 => (4, 1),
+                52..=61 => (5, 2),
+                62..=71 => (6, 2),
+                72..=85 => (9, 1),
+                86..=89 => (99, 0),
+                _ => {
+                    prog.push(src.range(-100, 30_000));
+                    continue;
+                }
+            };
+
+            let mut word = op;
+            let mut args = vec![];
+            let mut scale = 100;
+            for i in 0..params {
+                let write = i == 2 || op == 3;
+                let m = mode(src, write);
+                word += m * scale;
+                scale *= 10;
+                // Jump targets mostly land somewhere in the program
+                let v = if (op == 5 || op == 6) && i == 1 && m == 1 {
+                    src.range(0, len)
+                } else {
+                    operand(src, m, len)
+                };
+                args.push(v);
+            }
+            prog.push(word);
+            prog.extend(args);
+        }
+
+        let input = (0..src.below(17)).map(|_| src.range(-5, 50)).collect();
+        Case { prog, input }
+    }
+
+    /// Case for a fuzzer's raw input
+    pub fn from_bytes(data: &[u8]) -> Case {
+        Case::generate(&mut Bytes(data))
+    }
+}
+
+/// A way of running programs that has to match the interpreter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subject {
+    Interp,
+    Cached,
+    // The interpreter with tracing, profiling and taint tracking on, which
+    // goes through a different path in `Cpu::step`
+    Traced,
+}
+
+pub const SUBJECTS: [Subject; 3] = [Subject::Interp, Subject::Cached, Subject::Traced];
+
+/// Everything observable at the end of a run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub outputs: Vec<i64>,
+    pub status: Status,
+    pub pc: usize,
+    pub rel: i64,
+    pub mem: Vec<i64>,
+    pub sparse: Vec<(usize, i64)>,
+}
+
+pub fn run(case: &Case, subject: Subject) -> Outcome {
+    let prog = case.prog.clone();
+    let mut cpu = match subject {
+        Subject::Interp => Cpu::with_mem(prog),
+        Subject::Cached => Cpu::with_engine(prog, Engine::Cached),
+        Subject::Traced => {
+            let mut cpu = Cpu::with_mem(prog);
+            cpu.start_trace();
+            cpu.start_profile();
+            cpu.start_taint();
+            let taint = cpu.taint.as_mut().unwrap();
+            let tag = taint.new_tag("input");
+            taint.input.insert(tag);
+            cpu
+        }
+    };
+    cpu.set_limits(Limits {
+        instructions: Some(STEPS),
+        ..Limits::default()
+    });
+
+    let mut outputs = vec![];
+    let status = cpu.run_io(Iter(case.input.iter().copied()), &mut outputs);
+    Outcome {
+        outputs,
+        status,
+        pc: cpu.pc,
+        rel: cpu.rel,
+        mem: cpu.mem.as_slice().to_vec(),
+        sparse: cpu.mem.sparse(),
+    }
+}
+
+/// First thing two outcomes disagree on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    Output {
+        index: usize,
+        expected: Option<i64>,
+        actual: Option<i64>,
+    },
+    Status {
+        expected: Status,
+        actual: Status,
+    },
+    Registers {
+        expected: (usize, i64),
+        actual: (usize, i64),
+    },
+    Memory {
+        addr: usize,
+        expected: i64,
+        actual: i64,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mismatch::Output {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "output {}: expected {:?}, got {:?}",
+                index, expected, actual
+            ),
+            Mismatch::Status { expected, actual } => {
+                write!(f, "ended with {:?}, expected {:?}", actual, expected)
+            }
+            Mismatch::Registers { expected, actual } => write!(
+                f,
+                "pc/rel {}/{}, expected {}/{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            Mismatch::Memory {
+                addr,
+                expected,
+                actual,
+            } => write!(f, "[{}] is {}, expected {}", addr, actual, expected),
+        }
+    }
+}
+
+fn mem_mismatch(expected: &Outcome, actual: &Outcome) -> Option<Mismatch> {
+    let cell = |o: &Outcome, a: usize| match o.mem.get(a) {
+        Some(&v) => v,
+        None => o.sparse.iter().find(|c| c.0 == a).map_or(0, |c| c.1),
+    };
+    let mut addrs: Vec<usize> = (0..expected.mem.len().max(actual.mem.len())).collect();
+    addrs.extend(expected.sparse.iter().chain(&actual.sparse).map(|c| c.0));
+    addrs.sort_unstable();
+
+    addrs.into_iter().find_map(|addr| {
+        let (e, a) = (cell(expected, addr), cell(actual, addr));
+        if e != a {
+            Some(Mismatch::Memory {
+                addr,
+                expected: e,
+                actual: a,
+            })
+        } else {
+            None
+        }
+    })
+}
+
+impl Outcome {
+    pub fn mismatch(&self, actual: &Outcome) -> Option<Mismatch> {
+        let n = self.outputs.len().max(actual.outputs.len());
+        for index in 0..n {
+            let (e, a) = (self.outputs.get(index), actual.outputs.get(index));
+            if e != a {
+                return Some(Mismatch::Output {
+                    index,
+                    expected: e.copied(),
+                    actual: a.copied(),
+                });
+            }
+        }
+        if self.status != actual.status {
+            return Some(Mismatch::Status {
+                expected: self.status,
+                actual: actual.status,
+            });
+        }
+        if (self.pc, self.rel) != (actual.pc, actual.rel) {
+            return Some(Mismatch::Registers {
+                expected: (self.pc, self.rel),
+                actual: (actual.pc, actual.rel),
+            });
+        }
+        mem_mismatch(self, actual)
+    }
+}
+
+/// A subject that disagreed with the interpreter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub subject: Subject,
+    pub mismatch: Mismatch,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}: {}", self.subject, self.mismatch)
+    }
+}
+
+/// Run `case` on every subject against the interpreter
+pub fn check(case: &Case) -> Result<(), Divergence> {
+    let expected = run(case, Subject::Interp);
+    for &subject in &SUBJECTS[1..] {
+        if let Some(mismatch) = expected.mismatch(&run(case, subject)) {
+            return Err(Divergence { subject, mismatch });
+        }
+    }
+    Ok(())
+}
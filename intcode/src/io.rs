@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use crate::{Cpu, Status};
+
+/// Where a running cpu gets its input from
+pub trait Input {
+    /// The next value, `None` if there isn't one. Channels block until a
+    /// value arrives or every sender is gone.
+    fn next_input(&mut self) -> Option<i64>;
+}
+
+/// Where a running cpu puts its output
+pub trait Output {
+    fn put(&mut self, v: i64);
+}
+
+/// Input from an iterator
+pub struct Iter<I>(pub I);
+
+/// Input from calling `FnMut() -> Option<i64>`, or output to `FnMut(i64)`
+pub struct Func<F>(pub F);
+
+impl<T: Input + ?Sized> Input for &mut T {
+    fn next_input(&mut self) -> Option<i64> {
+        (**self).next_input()
+    }
+}
+
+impl Input for VecDeque<i64> {
+    fn next_input(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+}
+
+impl Input for Receiver<i64> {
+    fn next_input(&mut self) -> Option<i64> {
+        self.recv().ok()
+    }
+}
+
+impl<I: Iterator<Item = i64>> Input for Iter<I> {
+    fn next_input(&mut self) -> Option<i64> {
+        self.0.next()
+    }
+}
+
+impl<F: FnMut() -> Option<i64>> Input for Func<F> {
+    fn next_input(&mut self) -> Option<i64> {
+        (self.0)()
+    }
+}
+
+impl<T: Output + ?Sized> Output for &mut T {
+    fn put(&mut self, v: i64) {
+        (**self).put(v)
+    }
+}
+
+impl Output for Vec<i64> {
+    fn put(&mut self, v: i64) {
+        self.push(v)
+    }
+}
+
+impl Output for VecDeque<i64> {
+    fn put(&mut self, v: i64) {
+        self.push_back(v)
+    }
+}
+
+// Nobody listening is not the cpu's problem, the output is dropped
+impl Output for Sender<i64> {
+    fn put(&mut self, v: i64) {
+        self.send(v).ok();
+    }
+}
+
+impl Output for SyncSender<i64> {
+    fn put(&mut self, v: i64) {
+        self.send(v).ok();
+    }
+}
+
+impl<F: FnMut(i64)> Output for Func<F> {
+    fn put(&mut self, v: i64) {
+        (self.0)(v)
+    }
+}
+
+impl Cpu {
+    /// Run until halt or a fault, reading from `input` and writing to
+    /// `output`. `NeedsInput` means `input` ran out. A value read for an
+    /// `In` that faults or hits a limit is kept, and fed to it first when
+    /// `run_io` picks up again.
+    pub fn run_io(&mut self, mut input: impl Input, mut output: impl Output) -> Status {
+        let mut feed = self.pending.take();
+        loop {
+            match self.run(&mut feed) {
+                Status::Output(v) => output.put(v),
+                Status::NeedsInput => match input.next_input() {
+                    Some(v) => feed = Some(v),
+                    None => return Status::NeedsInput,
+                },
+                s => {
+                    self.pending = feed;
+                    return s;
+                }
+            }
+        }
+    }
+
+    /// `run_io` on a thread of its own, the cpu comes back when it's done
+    pub fn spawn<I, O>(mut self, input: I, output: O) -> JoinHandle<(Cpu, Status)>
+    where
+        I: Input + Send + 'static,
+        O: Output + Send + 'static,
+    {
+        thread::spawn(move || {
+            let status = self.run_io(input, output);
+            (self, status)
+        })
+    }
+}
@@ -0,0 +1,164 @@
+use std::convert::{TryFrom, TryInto};
+
+pub mod ascii;
+pub mod asm;
+mod cache;
+pub mod cfg;
+mod cpu;
+pub mod debug;
+pub mod disasm;
+pub mod fuzz;
+pub mod io;
+mod mem;
+pub mod profile;
+pub mod snapshot;
+pub mod symbolic;
+pub mod taint;
+pub mod trace;
+pub mod transpile;
+
+pub use cpu::{Cpu, Engine, Fault, Limit, Limits, Status, Step};
+pub use mem::{ErrorMemory, Memory};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Pos,
+    Im,
+    Rel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Intcode {
+    Add(Mode, Mode, Mode),
+    Mult(Mode, Mode, Mode),
+    In(Mode),
+    Out(Mode),
+    Jit(Mode, Mode),
+    Jif(Mode, Mode),
+    Lt(Mode, Mode, Mode),
+    Equ(Mode, Mode, Mode),
+    Adj(Mode),
+    Halt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorIntcode {
+    InvalidOpcode,
+    InvalidMode,
+}
+
+impl TryFrom<i64> for Mode {
+    type Error = ErrorIntcode;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Mode::Pos),
+            1 => Ok(Mode::Im),
+            2 => Ok(Mode::Rel),
+            _ => Err(ErrorIntcode::InvalidMode),
+        }
+    }
+}
+
+impl TryFrom<i64> for Intcode {
+    type Error = ErrorIntcode;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        let m1: Mode = ((value / 100) % 10).try_into()?;
+        let m2: Mode = ((value / 1000) % 10).try_into()?;
+        let m3: Mode = ((value / 10000) % 10).try_into()?;
+
+        match value % 100 {
+            1 => Ok(Intcode::Add(m1, m2, m3)),
+            2 => Ok(Intcode::Mult(m1, m2, m3)),
+            3 => Ok(Intcode::In(m1)),
+            4 => Ok(Intcode::Out(m1)),
+            5 => Ok(Intcode::Jit(m1, m2)),
+            6 => Ok(Intcode::Jif(m1, m2)),
+            7 => Ok(Intcode::Lt(m1, m2, m3)),
+            8 => Ok(Intcode::Equ(m1, m2, m3)),
+            9 => Ok(Intcode::Adj(m1)),
+            99 => Ok(Intcode::Halt),
+            _ => Err(ErrorIntcode::InvalidOpcode),
+        }
+    }
+}
+
+impl Intcode {
+    /// Number of words the instruction occupies, opcode included
+    pub fn size(self) -> usize {
+        match self {
+            Intcode::Add(..) | Intcode::Mult(..) | Intcode::Lt(..) | Intcode::Equ(..) => 4,
+            Intcode::Jit(..) | Intcode::Jif(..) => 3,
+            Intcode::In(_) | Intcode::Out(_) | Intcode::Adj(_) => 2,
+            Intcode::Halt => 1,
+        }
+    }
+
+    pub fn opcode(self) -> i64 {
+        match self {
+            Intcode::Add(..) => 1,
+            Intcode::Mult(..) => 2,
+            Intcode::In(_) => 3,
+            Intcode::Out(_) => 4,
+            Intcode::Jit(..) => 5,
+            Intcode::Jif(..) => 6,
+            Intcode::Lt(..) => 7,
+            Intcode::Equ(..) => 8,
+            Intcode::Adj(_) => 9,
+            Intcode::Halt => 99,
+        }
+    }
+
+    /// Modes of the parameters, in order
+    pub fn modes(self) -> Vec<Mode> {
+        match self {
+            Intcode::Add(m1, m2, m3)
+            | Intcode::Mult(m1, m2, m3)
+            | Intcode::Lt(m1, m2, m3)
+            | Intcode::Equ(m1, m2, m3) => vec![m1, m2, m3],
+            Intcode::Jit(m1, m2) | Intcode::Jif(m1, m2) => vec![m1, m2],
+            Intcode::In(m1) | Intcode::Out(m1) | Intcode::Adj(m1) => vec![m1],
+            Intcode::Halt => vec![],
+        }
+    }
+
+    /// The canonical instruction word, unused mode digits left at 0
+    pub fn encode(self) -> i64 {
+        let mut word = self.opcode();
+        let mut scale = 100;
+        for m in self.modes() {
+            word += i64::from(m) * scale;
+            scale *= 10;
+        }
+        word
+    }
+}
+
+impl From<Mode> for i64 {
+    fn from(m: Mode) -> i64 {
+        match m {
+            Mode::Pos => 0,
+            Mode::Im => 1,
+            Mode::Rel => 2,
+        }
+    }
+}
+
+/// Parse a comma separated program
+pub fn parse_mem(text: &str) -> Vec<i64> {
+    let text = text.trim();
+    text.split(',').map(|s| s.trim().parse().unwrap()).collect()
+}
+
+/// Inverse of `parse_mem`
+pub fn format_mem(mem: &[i64]) -> String {
+    let words: Vec<String> = mem.iter().map(|w| w.to_string()).collect();
+    words.join(",")
+}
+
+/// Load the program from the `input` file in the working directory
+pub fn read_mem() -> Vec<i64> {
+    let text = std::fs::read_to_string("input").unwrap();
+    parse_mem(&text)
+}
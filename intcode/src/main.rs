@@ -0,0 +1,272 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use intcode::cfg::Cfg;
+use intcode::debug::Debugger;
+use intcode::fuzz::{self, Case, Rng};
+use intcode::snapshot::Snapshot;
+use intcode::{asm, disasm, format_mem, parse_mem, trace, transpile, Cpu, Status};
+
+const USAGE: &str = "usage: intcode <command> [file]
+
+commands:
+    disasm [file]    print an annotated listing of the program (default: input)
+    asm <file>       assemble a source file into the comma separated format
+    debug [file]     step through the program interactively, `help` lists commands
+    debug --load <snapshot>
+                     resume debugging from a snapshot written by `save`
+    trace <file> <trace> [in,...]
+                     run the program with the given input, recording every
+                     instruction to <trace> as JSON lines
+    replay <file> <trace>
+                     re-run the program against a recorded trace and report
+                     where it first diverges
+    profile <file> [in,...] [--folded <out>]
+                     run the program and report where the time goes, with
+                     --folded also write folded stacks for flamegraph tools
+    cfg [file] [--calls]
+                     print the control flow graph as Graphviz source, with
+                     --calls only the call graph between functions
+    transpile [file] [--preset addr,...]
+                     print a Rust module that runs the program natively,
+                     --preset lists words written before it starts
+    fuzz [count] [seed]
+                     run random programs through every engine and stop at
+                     the first one that disagrees with the interpreter";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1);
+}
+
+fn read(path: &str) -> String {
+    match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn load(path: Option<&String>) -> Vec<i64> {
+    let path = path.map(|s| s.as_str()).unwrap_or("input");
+    parse_mem(&read(path))
+}
+
+fn assemble(path: Option<&String>) {
+    let path = path.unwrap_or_else(|| usage());
+    match asm::assemble(&read(path)) {
+        Ok(mem) => println!("{}", format_mem(&mem)),
+        Err(e) => {
+            eprintln!("{}:{}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn debug(args: &[String]) {
+    let mut dbg = match args {
+        [flag, snap] if flag == "--load" => {
+            let mut dbg = Debugger::new(Cpu::with_mem(vec![]));
+            match Snapshot::load(snap) {
+                Ok(s) => dbg.restore(s),
+                Err(e) => {
+                    eprintln!("{}: {}", snap, e);
+                    process::exit(1);
+                }
+            }
+            dbg
+        }
+        [] | [_] => Debugger::new(Cpu::with_mem(load(args.first()))),
+        _ => usage(),
+    };
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        print!("(icdb) ");
+        io::stdout().flush().unwrap();
+        let line = match lines.next() {
+            Some(l) => l.unwrap(),
+            None => break,
+        };
+        let cmd = line.trim();
+        if cmd == "q" || cmd == "quit" {
+            break;
+        }
+        print!("{}", dbg.command(cmd));
+    }
+}
+
+// Run until halt, a fault or running out of the comma separated `input`,
+// printing outputs as they come
+fn run_with(cpu: &mut Cpu, input: Option<&String>) -> Status {
+    let mut input: Vec<i64> = match input {
+        Some(s) => parse_mem(s),
+        None => vec![],
+    };
+    input.reverse();
+
+    let mut feed = input.pop();
+    loop {
+        match cpu.run(&mut feed) {
+            Status::Output(v) => println!("{}", v),
+            Status::NeedsInput => match input.pop() {
+                Some(v) => feed = Some(v),
+                None => return Status::NeedsInput,
+            },
+            s => return s,
+        }
+    }
+}
+
+fn record(args: &[String]) {
+    let (path, out) = match args {
+        [path, out, ..] => (path, out),
+        _ => usage(),
+    };
+
+    let mut cpu = Cpu::with_mem(load(Some(path)));
+    cpu.start_trace();
+    let status = run_with(&mut cpu, args.get(2));
+
+    let events = cpu.take_trace();
+    let written =
+        std::fs::File::create(out).and_then(|f| trace::write(&mut io::BufWriter::new(f), &events));
+    if let Err(e) = written {
+        eprintln!("{}: {}", out, e);
+        process::exit(1);
+    }
+    eprintln!("{} instructions, {:?}", events.len(), status);
+}
+
+fn replay(args: &[String]) {
+    let (path, recorded) = match args {
+        [path, recorded] => (path, recorded),
+        _ => usage(),
+    };
+    let events = match trace::read(&read(recorded)) {
+        Ok(events) => events,
+        Err(e) => {
+            eprintln!("{}:{}", recorded, e);
+            process::exit(1);
+        }
+    };
+
+    let mut cpu = Cpu::with_mem(load(Some(path)));
+    match trace::replay(&mut cpu, &events) {
+        Ok(n) => println!("{} instructions match", n),
+        Err(d) => {
+            println!("{}", d);
+            process::exit(1);
+        }
+    }
+}
+
+fn profile(args: &[String]) {
+    let (mut rest, mut folded) = (vec![], None);
+    let mut args = args.iter();
+    while let Some(a) = args.next() {
+        match a.as_str() {
+            "--folded" => folded = Some(args.next().unwrap_or_else(|| usage())),
+            _ => rest.push(a),
+        }
+    }
+    let (path, input) = match rest[..] {
+        [path] => (path, None),
+        [path, input] => (path, Some(input)),
+        _ => usage(),
+    };
+
+    let prog = load(Some(path));
+    let mut cpu = Cpu::with_mem(prog.clone());
+    cpu.start_profile();
+    let status = run_with(&mut cpu, input);
+    let profile = cpu.take_profile();
+
+    println!("{:?} after {}", status, profile.report(&prog, 20));
+    if let Some(out) = folded {
+        if let Err(e) = std::fs::write(out, profile.folded()) {
+            eprintln!("{}: {}", out, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn cfg(args: &[String]) {
+    let (path, calls) = match args {
+        [] => (None, false),
+        [flag] if flag == "--calls" => (None, true),
+        [path] => (Some(path), false),
+        [path, flag] if flag == "--calls" => (Some(path), true),
+        _ => usage(),
+    };
+    let mem = load(path);
+    let cfg = Cfg::recover(&mem);
+    if calls {
+        print!("{}", cfg.call_graph_dot());
+    } else {
+        print!("{}", cfg.to_dot(&mem));
+    }
+}
+
+fn transpile(args: &[String]) {
+    let (path, preset) = match args {
+        [] => (None, None),
+        [flag, preset] if flag == "--preset" => (None, Some(preset)),
+        [path] => (Some(path), None),
+        [path, flag, preset] if flag == "--preset" => (Some(path), Some(preset)),
+        _ => usage(),
+    };
+    let preset: Vec<usize> = match preset {
+        Some(s) => s
+            .split(',')
+            .map(|a| a.trim().parse().unwrap_or_else(|_| usage()))
+            .collect(),
+        None => vec![],
+    };
+    match transpile::transpile(&load(path), &preset) {
+        Ok(src) => print!("{}", src),
+        Err(e) => {
+            eprintln!("Can't transpile: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn fuzz(args: &[String]) {
+    let num = |i: usize, default: u64| match args.get(i) {
+        Some(s) => s.parse().unwrap_or_else(|_| usage()),
+        None => default,
+    };
+    let (count, seed) = (num(0, 100_000), num(1, 0));
+    for seed in (0..count).map(|i| seed.wrapping_add(i)) {
+        let case = Case::generate(&mut Rng::new(seed));
+        if let Err(d) = fuzz::check(&case) {
+            println!("seed {}: {}", seed, d);
+            println!("program: {}", format_mem(&case.prog));
+            println!("input: {}", format_mem(&case.input));
+            process::exit(1);
+        }
+    }
+    println!("{} cases, no divergence", count);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.first().map(|s| s.as_str()) {
+        Some("disasm") => print!("{}", disasm::listing(&load(args.get(1)))),
+        Some("asm") => assemble(args.get(1)),
+        Some("debug") => debug(&args[1..]),
+        Some("trace") => record(&args[1..]),
+        Some("replay") => replay(&args[1..]),
+        Some("profile") => profile(&args[1..]),
+        Some("cfg") => cfg(&args[1..]),
+        Some("transpile") => transpile(&args[1..]),
+        Some("fuzz") => fuzz(&args[1..]),
+        _ => usage(),
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::ops::{Index, IndexMut};
+
+// Writes this close past the end of the dense part just grow it, anything
+// further away goes into the sparse map
+const GROW_SLACK: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorMemory {
+    OverLimit(usize),
+}
+
+/// Intcode memory. Unset cells read as 0 and writes to any address grow it,
+/// up to an optional hard limit.
+#[derive(Debug, Clone, Default)]
+pub struct Memory {
+    dense: Vec<i64>,
+    sparse: HashMap<usize, i64>,
+    limit: Option<usize>,
+    // Addresses written since they were last drained, while tracking
+    writes: Option<Vec<usize>>,
+}
+
+impl Memory {
+    pub fn new(prog: Vec<i64>) -> Memory {
+        Memory {
+            dense: prog,
+            sparse: HashMap::new(),
+            limit: None,
+            writes: None,
+        }
+    }
+
+    /// Refuse writes at or above `limit`
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    pub fn get(&self, addr: usize) -> i64 {
+        match self.dense.get(addr) {
+            Some(v) => *v,
+            None => *self.sparse.get(&addr).unwrap_or(&0),
+        }
+    }
+
+    pub fn set(&mut self, addr: usize, v: i64) -> Result<(), ErrorMemory> {
+        *self.cell(addr)? = v;
+        Ok(())
+    }
+
+    /// Length of the contiguous part, the loaded program plus whatever it grew into
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty() && self.sparse.is_empty()
+    }
+
+    /// The contiguous part of memory, see `len`
+    pub fn as_slice(&self) -> &[i64] {
+        &self.dense
+    }
+
+    /// Cells stored outside the contiguous part, sorted by address
+    pub fn sparse(&self) -> Vec<(usize, i64)> {
+        let mut cells: Vec<(usize, i64)> = self.sparse.iter().map(|(&a, &v)| (a, v)).collect();
+        cells.sort_unstable();
+        cells
+    }
+
+    /// Remember which addresses get written, for caches of decoded code
+    /// that need to notice self-modification. Turning it off forgets them.
+    pub fn track_writes(&mut self, on: bool) {
+        if !on {
+            self.writes = None;
+        } else if self.writes.is_none() {
+            self.writes = Some(vec![]);
+        }
+    }
+
+    pub fn tracking_writes(&self) -> bool {
+        self.writes.is_some()
+    }
+
+    /// Hand every address written since the last drain to `f`
+    pub fn drain_writes(&mut self, mut f: impl FnMut(usize)) {
+        if let Some(w) = self.writes.as_mut() {
+            for a in w.drain(..) {
+                f(a);
+            }
+        }
+    }
+
+    /// Number of cells actually backed by storage
+    pub fn footprint(&self) -> usize {
+        self.dense.len() + self.sparse.len()
+    }
+
+    fn cell(&mut self, addr: usize) -> Result<&mut i64, ErrorMemory> {
+        if let Some(limit) = self.limit {
+            if addr >= limit {
+                return Err(ErrorMemory::OverLimit(addr));
+            }
+        }
+
+        if let Some(w) = self.writes.as_mut() {
+            w.push(addr);
+        }
+
+        let len = self.dense.len();
+        if addr < len {
+            return Ok(&mut self.dense[addr]);
+        }
+
+        if addr - len < GROW_SLACK {
+            self.dense.resize(addr + 1, 0);
+            // Pull in anything that was stored sparsely in the new range
+            if !self.sparse.is_empty() {
+                let dense = &mut self.dense;
+                self.sparse.retain(|&a, v| {
+                    if a < dense.len() {
+                        dense[a] = *v;
+                        false
+                    } else {
+                        true
+                    }
+                });
+            }
+            Ok(&mut self.dense[addr])
+        } else {
+            Ok(self.sparse.entry(addr).or_insert(0))
+        }
+    }
+}
+
+impl From<Vec<i64>> for Memory {
+    fn from(prog: Vec<i64>) -> Memory {
+        Memory::new(prog)
+    }
+}
+
+impl Index<usize> for Memory {
+    type Output = i64;
+
+    fn index(&self, addr: usize) -> &i64 {
+        match self.dense.get(addr) {
+            Some(v) => v,
+            None => self.sparse.get(&addr).unwrap_or(&0),
+        }
+    }
+}
+
+impl IndexMut<usize> for Memory {
+    fn index_mut(&mut self, addr: usize) -> &mut i64 {
+        self.cell(addr).expect("Write over memory limit")
+    }
+}
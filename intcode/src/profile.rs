@@ -0,0 +1,134 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::disasm::{self, mnemonic};
+use crate::{Intcode, Mode};
+
+// Intcode has no call instruction, but compiled programs open a stack frame
+// with `ARB #n` on entry and drop it with `ARB #-n` before returning. Frames
+// are named after the pc of the `ARB` that opened them, which is the function
+// entry or close to it.
+
+/// Execution counts gathered while a cpu runs
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub total: u64,
+    pub per_pc: HashMap<usize, u64>,
+    // Keyed on the whole instruction so the counts split by mode combination
+    pub per_op: HashMap<Intcode, u64>,
+    pub per_stack: HashMap<Vec<usize>, u64>,
+    stack: Vec<usize>,
+}
+
+fn mode_name(m: Mode) -> &'static str {
+    match m {
+        Mode::Pos => "pos",
+        Mode::Im => "im",
+        Mode::Rel => "rel",
+    }
+}
+
+fn pct(n: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        n as f64 * 100.0 / total as f64
+    }
+}
+
+// Highest counts first, ties broken on the key so reports are stable
+fn sorted<K: Ord + Clone>(counts: impl Iterator<Item = (K, u64)>) -> Vec<(K, u64)> {
+    let mut v: Vec<(K, u64)> = counts.collect();
+    v.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    v
+}
+
+impl Profile {
+    /// Count one executed instruction, `rel` is how the relative base moved
+    pub fn record(&mut self, pc: usize, op: Intcode, rel: Ordering) {
+        self.total += 1;
+        *self.per_pc.entry(pc).or_insert(0) += 1;
+        *self.per_op.entry(op).or_insert(0) += 1;
+
+        match self.per_stack.get_mut(&self.stack[..]) {
+            Some(n) => *n += 1,
+            None => {
+                self.per_stack.insert(self.stack.clone(), 1);
+            }
+        }
+
+        if let Intcode::Adj(_) = op {
+            match rel {
+                Ordering::Greater => self.stack.push(pc),
+                Ordering::Less => {
+                    self.stack.pop();
+                }
+                Ordering::Equal => {}
+            }
+        }
+    }
+
+    /// Counts per opcode, all mode combinations added up
+    pub fn per_kind(&self) -> HashMap<&'static str, u64> {
+        let mut kinds = HashMap::new();
+        for (&op, &n) in &self.per_op {
+            *kinds.entry(mnemonic(op)).or_insert(0) += n;
+        }
+        kinds
+    }
+
+    /// Human readable report, `top` hottest addresses are listed against the
+    /// disassembly of `mem`
+    pub fn report(&self, mem: &[i64], top: usize) -> String {
+        let mut out = format!("{} instructions\n", self.total);
+
+        out.push_str("\nHot spots:\n");
+        for (pc, n) in sorted(self.per_pc.iter().map(|(&k, &v)| (k, v)))
+            .into_iter()
+            .take(top)
+        {
+            let text = match disasm::disassemble_from(mem, pc, 1).first() {
+                Some(line) => line.text(),
+                None => String::new(),
+            };
+            let pct = pct(n, self.total);
+            out.push_str(&format!("{:>12} {:>6.2}% {:>6}: {}\n", n, pct, pc, text));
+        }
+
+        out.push_str("\nBy opcode:\n");
+        for (name, n) in sorted(self.per_kind().into_iter()) {
+            let pct = pct(n, self.total);
+            out.push_str(&format!("{:>12} {:>6.2}% {}\n", n, pct, name));
+        }
+
+        out.push_str("\nBy mode combination:\n");
+        let combos = self.per_op.iter().map(|(&op, &n)| {
+            let modes: Vec<&str> = op.modes().into_iter().map(mode_name).collect();
+            (format!("{:<4}{}", mnemonic(op), modes.join(",")), n)
+        });
+        for (name, n) in sorted(combos) {
+            let pct = pct(n, self.total);
+            out.push_str(&format!("{:>12} {:>6.2}% {}\n", n, pct, name.trim_end()));
+        }
+
+        out
+    }
+
+    /// Folded stacks, one `main;@12;@340 count` line per stack, the input
+    /// format flamegraph tools expect
+    pub fn folded(&self) -> String {
+        let stacks = self.per_stack.iter().map(|(stack, &n)| {
+            let mut name = String::from("main");
+            for pc in stack {
+                name.push_str(&format!(";@{}", pc));
+            }
+            (name, n)
+        });
+
+        let mut out = String::new();
+        for (name, n) in sorted(stacks) {
+            out.push_str(&format!("{} {}\n", name, n));
+        }
+        out
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::{format_mem, Cpu, Engine, Memory};
+
+// Plain text, one field per line, after a version header:
+//
+//     intcode-snapshot 1
+//     engine cached
+//     pc 1033
+//     rel 3101
+//     pending 110
+//     limit 65536
+//     input 111
+//     output 10,10
+//     mem 109,4818,21101,...
+//     sparse 100000=5,250000=-1
+//
+// `engine` is `interp` or `cached`, and `pending` the input `Cpu::run_io`
+// read for an instruction that didn't get to run. Those two, `limit` and
+// `sparse` are left out when there's nothing to put in them, an engine
+// isn't needed for the interpreter. Unknown fields are an error rather than
+// silently dropped.
+
+pub const VERSION: u32 = 1;
+const MAGIC: &str = "intcode-snapshot";
+
+#[derive(Debug)]
+pub enum ErrorSnapshot {
+    Io(io::Error),
+    // Not a snapshot, or one written by a newer version
+    Version(String),
+    Parse { line: usize, msg: String },
+}
+
+impl fmt::Display for ErrorSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorSnapshot::Io(e) => write!(f, "{}", e),
+            ErrorSnapshot::Version(v) => write!(f, "unsupported snapshot header `{}`", v),
+            ErrorSnapshot::Parse { line, msg } => write!(f, "line {}: {}", line, msg),
+        }
+    }
+}
+
+impl std::error::Error for ErrorSnapshot {}
+
+impl From<io::Error> for ErrorSnapshot {
+    fn from(e: io::Error) -> ErrorSnapshot {
+        ErrorSnapshot::Io(e)
+    }
+}
+
+/// Everything needed to resume a program: the cpu plus input queued for it
+/// and output it produced that the harness hasn't dealt with yet
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub cpu: Cpu,
+    pub input: Vec<i64>,
+    pub output: Vec<i64>,
+}
+
+fn parse_list(s: &str) -> Result<Vec<i64>, String> {
+    if s.is_empty() {
+        return Ok(vec![]);
+    }
+    s.split(',')
+        .map(|v| v.trim().parse().map_err(|_| format!("Bad number `{}`", v)))
+        .collect()
+}
+
+fn engine_name(engine: Engine) -> &'static str {
+    match engine {
+        Engine::Interp => "interp",
+        Engine::Cached => "cached",
+    }
+}
+
+fn parse_engine(s: &str) -> Result<Engine, String> {
+    match s {
+        "interp" => Ok(Engine::Interp),
+        "cached" => Ok(Engine::Cached),
+        _ => Err(format!("Unknown engine `{}`", s)),
+    }
+}
+
+fn parse_cell(s: &str) -> Result<(usize, i64), String> {
+    let bad = || format!("Bad cell `{}`, expected addr=value", s);
+    let mut parts = s.trim().splitn(2, '=');
+    let addr = parts.next().and_then(|a| a.parse().ok()).ok_or_else(bad)?;
+    let v = parts.next().and_then(|v| v.parse().ok()).ok_or_else(bad)?;
+    Ok((addr, v))
+}
+
+impl Snapshot {
+    pub fn new(cpu: Cpu) -> Snapshot {
+        Snapshot {
+            cpu,
+            input: vec![],
+            output: vec![],
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mem = &self.cpu.mem;
+        let mut out = format!("{} {}\n", MAGIC, VERSION);
+        if self.cpu.engine() != Engine::Interp {
+            out.push_str(&format!("engine {}\n", engine_name(self.cpu.engine())));
+        }
+        out.push_str(&format!("pc {}\nrel {}\n", self.cpu.pc, self.cpu.rel));
+        if let Some(v) = self.cpu.pending {
+            out.push_str(&format!("pending {}\n", v));
+        }
+        if let Some(limit) = mem.limit() {
+            out.push_str(&format!("limit {}\n", limit));
+        }
+        out.push_str(&format!("input {}\n", format_mem(&self.input)));
+        out.push_str(&format!("output {}\n", format_mem(&self.output)));
+        out.push_str(&format!("mem {}\n", format_mem(mem.as_slice())));
+
+        let sparse: Vec<String> = mem
+            .sparse()
+            .into_iter()
+            .map(|(a, v)| format!("{}={}", a, v))
+            .collect();
+        if !sparse.is_empty() {
+            out.push_str(&format!("sparse {}\n", sparse.join(",")));
+        }
+        out
+    }
+
+    pub fn parse(text: &str) -> Result<Snapshot, ErrorSnapshot> {
+        let mut lines = text.lines().enumerate();
+
+        let header = lines.next().map(|(_, l)| l.trim()).unwrap_or("");
+        match header.split_whitespace().collect::<Vec<_>>()[..] {
+            [MAGIC, v] if v.parse().is_ok_and(|v| (1..=VERSION).contains(&v)) => {}
+            _ => return Err(ErrorSnapshot::Version(header.to_string())),
+        }
+
+        let mut engine = Engine::Interp;
+        let mut pc = None;
+        let mut rel = None;
+        let mut pending = None;
+        let mut limit = None;
+        let mut input = vec![];
+        let mut output = vec![];
+        let mut mem = None;
+        let mut sparse = vec![];
+
+        for (idx, line) in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, val) = match line.find(' ') {
+                Some(i) => (&line[..i], line[i + 1..].trim()),
+                None => (line, ""),
+            };
+            let parsed = match key {
+                "engine" => parse_engine(val).map(|e| engine = e),
+                "pc" => val
+                    .parse()
+                    .map(|v| pc = Some(v))
+                    .map_err(|_| "Bad pc".into()),
+                "rel" => val
+                    .parse()
+                    .map(|v| rel = Some(v))
+                    .map_err(|_| "Bad rel".into()),
+                "pending" => val
+                    .parse()
+                    .map(|v| pending = Some(v))
+                    .map_err(|_| "Bad pending input".into()),
+                "limit" => val
+                    .parse()
+                    .map(|v| limit = Some(v))
+                    .map_err(|_| "Bad limit".into()),
+                "input" => parse_list(val).map(|v| input = v),
+                "output" => parse_list(val).map(|v| output = v),
+                "mem" => parse_list(val).map(|v| mem = Some(v)),
+                "sparse" => val
+                    .split(',')
+                    .map(parse_cell)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(|v| sparse = v),
+                _ => Err(format!("Unknown field `{}`", key)),
+            };
+            parsed.map_err(|msg| ErrorSnapshot::Parse { line: idx + 1, msg })?;
+        }
+
+        let missing = |what: &str| ErrorSnapshot::Parse {
+            line: 0,
+            msg: format!("Missing {}", what),
+        };
+        let mut mem = Memory::new(mem.ok_or_else(|| missing("mem"))?);
+        for (a, v) in sparse {
+            mem[a] = v;
+        }
+        mem.set_limit(limit);
+
+        let mut cpu = Cpu::with_engine(vec![], engine);
+        cpu.mem = mem;
+        cpu.pc = pc.ok_or_else(|| missing("pc"))?;
+        cpu.rel = rel.ok_or_else(|| missing("rel"))?;
+        cpu.pending = pending;
+
+        Ok(Snapshot { cpu, input, output })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Snapshot, ErrorSnapshot> {
+        Snapshot::parse(&fs::read_to_string(path)?)
+    }
+}
+
+impl Cpu {
+    /// Save just the cpu, see `Snapshot` for keeping pending I/O too
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        Snapshot::new(self.clone()).save(path)
+    }
+
+    /// Load a cpu saved with `save`, any pending I/O in the file is dropped
+    pub fn load(path: impl AsRef<Path>) -> Result<Cpu, ErrorSnapshot> {
+        Ok(Snapshot::load(path)?.cpu)
+    }
+}
@@ -0,0 +1,780 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use crate::cpu::{to_addr, Fault};
+use crate::{ErrorIntcode, Intcode, Mode};
+
+// Runs a program with some memory cells and inputs left as symbols, each with
+// a small domain of values it can take. Arithmetic on symbols builds
+// expression trees, a jump on a symbolic condition forks the path, and
+// anything that has to be concrete (opcodes, write addresses, jump targets,
+// `ARB`) forks once per value it can take. Reads from symbolic addresses
+// don't fork, they keep a copy of memory and pick the word when evaluated.
+//
+// Solving looks for symbol values that make a memory cell or an output equal
+// a target on some path. Targets linear in the symbols are solved for one
+// symbol directly, everything else is enumerated over the domains.
+
+pub type Value = Rc<Expr>;
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Const(i64),
+    Sym(usize),
+    Add(Value, Value),
+    Mul(Value, Value),
+    Lt(Value, Value),
+    Eq(Value, Value),
+    // Word at a symbolic address in memory as it was when read
+    Load(Value, Rc<Vec<Value>>),
+}
+
+impl PartialEq for Expr {
+    fn eq(&self, other: &Expr) -> bool {
+        match (self, other) {
+            (Expr::Const(a), Expr::Const(b)) => a == b,
+            (Expr::Sym(a), Expr::Sym(b)) => a == b,
+            (Expr::Add(a1, b1), Expr::Add(a2, b2))
+            | (Expr::Mul(a1, b1), Expr::Mul(a2, b2))
+            | (Expr::Lt(a1, b1), Expr::Lt(a2, b2))
+            | (Expr::Eq(a1, b1), Expr::Eq(a2, b2)) => a1 == a2 && b1 == b2,
+            (Expr::Load(a1, m1), Expr::Load(a2, m2)) => a1 == a2 && Rc::ptr_eq(m1, m2),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Const(v) => write!(f, "{}", v),
+            Expr::Sym(s) => write!(f, "s{}", s),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Mul(a, b) => write!(f, "({} * {})", a, b),
+            Expr::Lt(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Eq(a, b) => write!(f, "({} == {})", a, b),
+            Expr::Load(a, _) => write!(f, "[{}]", a),
+        }
+    }
+}
+
+fn lit(v: i64) -> Value {
+    Rc::new(Expr::Const(v))
+}
+
+fn add(a: Value, b: Value) -> Value {
+    match (&*a, &*b) {
+        (&Expr::Const(x), &Expr::Const(y)) if x.checked_add(y).is_some() => lit(x + y),
+        (Expr::Const(0), _) => b,
+        (_, Expr::Const(0)) => a,
+        _ => Rc::new(Expr::Add(a, b)),
+    }
+}
+
+fn mul(a: Value, b: Value) -> Value {
+    match (&*a, &*b) {
+        (&Expr::Const(x), &Expr::Const(y)) if x.checked_mul(y).is_some() => lit(x * y),
+        (Expr::Const(0), _) | (_, Expr::Const(0)) => lit(0),
+        (Expr::Const(1), _) => b,
+        (_, Expr::Const(1)) => a,
+        _ => Rc::new(Expr::Mul(a, b)),
+    }
+}
+
+fn lt(a: Value, b: Value) -> Value {
+    match (&*a, &*b) {
+        (Expr::Const(x), Expr::Const(y)) => lit((x < y) as i64),
+        _ if a == b => lit(0),
+        _ => Rc::new(Expr::Lt(a, b)),
+    }
+}
+
+fn eq(a: Value, b: Value) -> Value {
+    match (&*a, &*b) {
+        (Expr::Const(x), Expr::Const(y)) => lit((x == y) as i64),
+        _ if a == b => lit(1),
+        _ => Rc::new(Expr::Eq(a, b)),
+    }
+}
+
+// `k + sum(c * s)` from two linear forms
+fn merge(a: Linear, b: Linear) -> Option<Linear> {
+    let (k, mut terms) = a;
+    for (s, c) in b.1 {
+        let e = terms.entry(s).or_insert(0);
+        *e = e.checked_add(c)?;
+    }
+    terms.retain(|_, c| *c != 0);
+    Some((k.checked_add(b.0)?, terms))
+}
+
+fn scale(a: Linear, f: i64) -> Option<Linear> {
+    let (k, terms) = a;
+    let mut scaled = BTreeMap::new();
+    for (s, c) in terms {
+        scaled.insert(s, c.checked_mul(f)?);
+    }
+    scaled.retain(|_, c| *c != 0);
+    Some((k.checked_mul(f)?, scaled))
+}
+
+/// Constant term and coefficient per symbol
+pub type Linear = (i64, BTreeMap<usize, i64>);
+
+impl Expr {
+    /// Value under `env`, indexed by symbol. `None` when evaluation would
+    /// overflow or read a negative address.
+    pub fn eval(&self, env: &[i64]) -> Option<i64> {
+        match self {
+            Expr::Const(v) => Some(*v),
+            Expr::Sym(s) => env.get(*s).copied(),
+            Expr::Add(a, b) => a.eval(env)?.checked_add(b.eval(env)?),
+            Expr::Mul(a, b) => a.eval(env)?.checked_mul(b.eval(env)?),
+            Expr::Lt(a, b) => Some((a.eval(env)? < b.eval(env)?) as i64),
+            Expr::Eq(a, b) => Some((a.eval(env)? == b.eval(env)?) as i64),
+            Expr::Load(a, mem) => match mem.get(usize::try_from(a.eval(env)?).ok()?) {
+                Some(v) => v.eval(env),
+                None => Some(0),
+            },
+        }
+    }
+
+    /// The expression as `k + sum(c * s)`, if it is one
+    pub fn linear(&self) -> Option<Linear> {
+        match self {
+            Expr::Const(v) => Some((*v, BTreeMap::new())),
+            Expr::Sym(s) => Some((0, vec![(*s, 1)].into_iter().collect())),
+            Expr::Add(a, b) => merge(a.linear()?, b.linear()?),
+            Expr::Mul(a, b) => {
+                let (a, b) = (a.linear()?, b.linear()?);
+                match (a.1.is_empty(), b.1.is_empty()) {
+                    (true, _) => scale(b, a.0),
+                    (_, true) => scale(a, b.0),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    pub fn symbols(&self, out: &mut BTreeSet<usize>) {
+        match self {
+            Expr::Const(_) => {}
+            Expr::Sym(s) => {
+                out.insert(*s);
+            }
+            Expr::Add(a, b) | Expr::Mul(a, b) | Expr::Lt(a, b) | Expr::Eq(a, b) => {
+                a.symbols(out);
+                b.symbols(out);
+            }
+            Expr::Load(a, mem) => {
+                a.symbols(out);
+                for v in mem.iter() {
+                    v.symbols(out);
+                }
+            }
+        }
+    }
+}
+
+/// `expr` is nonzero, or zero
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+    pub expr: Value,
+    pub nonzero: bool,
+}
+
+impl Constraint {
+    fn holds(&self, env: &[i64]) -> bool {
+        match self.expr.eval(env) {
+            Some(v) => (v != 0) == self.nonzero,
+            None => false,
+        }
+    }
+}
+
+/// How a path ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum End {
+    Halted,
+    // Out of input, and inputs aren't symbolic
+    NeedsInput,
+    Fault { pc: usize, kind: Fault },
+    StepLimit,
+}
+
+/// One way through the program and what has to hold for it to be taken
+#[derive(Debug, Clone)]
+pub struct Path {
+    pub mem: Vec<Value>,
+    pub outputs: Vec<Value>,
+    pub constraints: Vec<Constraint>,
+    // Symbols made for `In` on this path
+    pub inputs: usize,
+    pub end: End,
+}
+
+/// What `solve` should make equal the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    Mem(usize),
+    Output(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorSymbolic {
+    TooManyPaths,
+    // A value that had to be concrete has more candidates than can be tried
+    TooManyValues { pc: usize },
+    // More candidate solutions than can be tried
+    SearchTooLarge,
+}
+
+impl fmt::Display for ErrorSymbolic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorSymbolic::TooManyPaths => write!(f, "too many paths"),
+            ErrorSymbolic::TooManyValues { pc } => {
+                write!(f, "too many values to enumerate at {}", pc)
+            }
+            ErrorSymbolic::SearchTooLarge => write!(f, "too many candidates to search"),
+        }
+    }
+}
+
+impl std::error::Error for ErrorSymbolic {}
+
+// Most assignments tried when enumerating
+const MAX_ENUM: u128 = 1 << 22;
+// Most assignments tried to rule out a branch, it's kept if this runs out
+const MAX_CHECK: u128 = 1 << 16;
+// Most values a concrete-only operand can fork into
+const MAX_FORK: usize = 256;
+const MAX_MEM: usize = 1 << 20;
+
+#[derive(Debug, Clone)]
+struct State {
+    mem: Rc<Vec<Value>>,
+    pc: usize,
+    rel: i64,
+    inputs: usize,
+    fed: usize,
+    outputs: Vec<Value>,
+    constraints: Vec<Constraint>,
+    steps: usize,
+}
+
+enum Next {
+    Continue,
+    End(End),
+    // Turned out infeasible
+    Dead,
+}
+
+impl State {
+    fn get(&self, addr: usize) -> Value {
+        match self.mem.get(addr) {
+            Some(v) => v.clone(),
+            None => lit(0),
+        }
+    }
+
+    fn set(&mut self, addr: usize, v: Value) -> Result<(), Fault> {
+        if addr >= MAX_MEM {
+            return Err(Fault::OverLimit(addr));
+        }
+        let mem = Rc::make_mut(&mut self.mem);
+        if addr >= mem.len() {
+            mem.resize(addr + 1, lit(0));
+        }
+        mem[addr] = v;
+        Ok(())
+    }
+
+    fn constrain(&mut self, expr: Value, nonzero: bool) {
+        self.constraints.push(Constraint { expr, nonzero });
+    }
+
+    // Address a parameter refers to, `None` for immediates
+    fn param_addr(&self, i: usize, m: Mode) -> Option<Value> {
+        let word = self.get(self.pc + i);
+        match m {
+            Mode::Pos => Some(word),
+            Mode::Im => None,
+            Mode::Rel => Some(add(word, lit(self.rel))),
+        }
+    }
+
+    fn read(&self, i: usize, m: Mode) -> Result<Value, Fault> {
+        let a = match self.param_addr(i, m) {
+            Some(a) => a,
+            None => return Ok(self.get(self.pc + i)),
+        };
+        match *a {
+            Expr::Const(a) => Ok(self.get(to_addr(a)?)),
+            _ => Ok(Rc::new(Expr::Load(a, self.mem.clone()))),
+        }
+    }
+}
+
+/// Symbolic executor for one program
+#[derive(Debug, Clone)]
+pub struct Executor {
+    mem: Vec<i64>,
+    domains: Vec<RangeInclusive<i64>>,
+    cells: Vec<(usize, usize)>,
+    input: Vec<i64>,
+    input_domain: Option<RangeInclusive<i64>>,
+    pub max_paths: usize,
+    pub max_steps: usize,
+}
+
+fn width(d: &RangeInclusive<i64>) -> u128 {
+    if d.end() < d.start() {
+        0
+    } else {
+        (i128::from(*d.end()) - i128::from(*d.start()) + 1) as u128
+    }
+}
+
+impl Executor {
+    pub fn new(mem: &[i64]) -> Executor {
+        Executor {
+            mem: mem.to_vec(),
+            domains: vec![],
+            cells: vec![],
+            input: vec![],
+            input_domain: None,
+            max_paths: 1024,
+            max_steps: 1_000_000,
+        }
+    }
+
+    /// Make the cell at `addr` a symbol taking values in `domain`, returns
+    /// the symbol's index into solutions
+    pub fn symbol_at(&mut self, addr: usize, domain: RangeInclusive<i64>) -> usize {
+        let s = self.domains.len();
+        self.domains.push(domain);
+        self.cells.push((addr, s));
+        s
+    }
+
+    /// Queue a concrete input, these are used before any symbolic ones
+    pub fn feed(&mut self, v: i64) {
+        self.input.push(v);
+    }
+
+    /// Once fed input runs out, every `In` reads a fresh symbol in `domain`.
+    /// They're numbered after the ones from `symbol_at`, in the order read.
+    pub fn symbolic_input(&mut self, domain: RangeInclusive<i64>) {
+        self.input_domain = Some(domain);
+    }
+
+    fn domain(&self, s: usize) -> RangeInclusive<i64> {
+        match self.domains.get(s) {
+            Some(d) => d.clone(),
+            // Input symbols are only made once there's a domain for them
+            None => self.input_domain.clone().unwrap(),
+        }
+    }
+
+    fn env(&self, inputs: usize) -> Vec<i64> {
+        (0..self.domains.len() + inputs)
+            .map(|s| *self.domain(s).start())
+            .collect()
+    }
+
+    // Call `f` on every assignment of `vars` until it returns true. `None`
+    // when there are more than `cap` assignments.
+    fn enumerate(
+        &self,
+        vars: &[usize],
+        env: &mut [i64],
+        cap: u128,
+        mut f: impl FnMut(&[i64]) -> bool,
+    ) -> Option<bool> {
+        let size = vars
+            .iter()
+            .fold(1u128, |n, &s| n.saturating_mul(width(&self.domain(s))));
+        if size > cap {
+            return None;
+        }
+        if size == 0 {
+            return Some(false);
+        }
+        for &s in vars {
+            env[s] = *self.domain(s).start();
+        }
+        loop {
+            if f(env) {
+                return Some(true);
+            }
+            let mut i = 0;
+            loop {
+                let s = match vars.get(i) {
+                    Some(&s) => s,
+                    None => return Some(false),
+                };
+                if env[s] < *self.domain(s).end() {
+                    env[s] += 1;
+                    break;
+                }
+                env[s] = *self.domain(s).start();
+                i += 1;
+            }
+        }
+    }
+
+    fn constraint_symbols(constraints: &[Constraint], out: &mut BTreeSet<usize>) {
+        for c in constraints {
+            c.expr.symbols(out);
+        }
+    }
+
+    // Can't be ruled out, either because an assignment works or because
+    // there are too many to try
+    fn feasible(&self, s: &State) -> bool {
+        let mut vars = BTreeSet::new();
+        Executor::constraint_symbols(&s.constraints, &mut vars);
+        let vars: Vec<usize> = vars.into_iter().collect();
+        let mut env = self.env(s.inputs);
+        let holds = |env: &[i64]| s.constraints.iter().all(|c| c.holds(env));
+        self.enumerate(&vars, &mut env, MAX_CHECK, holds)
+            .unwrap_or(true)
+    }
+
+    // Settle on a concrete value for `e`. Every other value it can take goes
+    // to `todo` as a copy of the state constrained to it, which runs the
+    // instruction again. `None` when no value is possible.
+    fn concrete(
+        &self,
+        s: &mut State,
+        e: Value,
+        todo: &mut Vec<State>,
+    ) -> Result<Option<i64>, ErrorSymbolic> {
+        if let Expr::Const(v) = *e {
+            return Ok(Some(v));
+        }
+        // Already settled on an earlier run of this instruction
+        for c in &s.constraints {
+            if let (Expr::Eq(a, b), true) = (&*c.expr, c.nonzero) {
+                if let (true, Expr::Const(v)) = (*a == e, &**b) {
+                    return Ok(Some(*v));
+                }
+            }
+        }
+
+        let mut vars = BTreeSet::new();
+        e.symbols(&mut vars);
+        Executor::constraint_symbols(&s.constraints, &mut vars);
+        let vars: Vec<usize> = vars.into_iter().collect();
+
+        let mut values = BTreeSet::new();
+        let mut env = self.env(s.inputs);
+        let too_many = ErrorSymbolic::TooManyValues { pc: s.pc };
+        let done = self.enumerate(&vars, &mut env, MAX_ENUM, |env| {
+            if s.constraints.iter().all(|c| c.holds(env)) {
+                if let Some(v) = e.eval(env) {
+                    values.insert(v);
+                }
+            }
+            values.len() > MAX_FORK
+        });
+        if done != Some(false) {
+            return Err(too_many);
+        }
+
+        let mut values = values.into_iter();
+        let first = match values.next() {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let rest: Vec<i64> = values.collect();
+        // A single possible value is already implied, no need to say so
+        if !rest.is_empty() {
+            for v in rest {
+                let mut t = s.clone();
+                t.constrain(eq(e.clone(), lit(v)), true);
+                todo.push(t);
+            }
+            s.constrain(eq(e, lit(first)), true);
+        }
+        Ok(Some(first))
+    }
+
+    // Whether `cond` is nonzero on this path, forking off the other case
+    // when both are possible
+    fn decide(&self, s: &mut State, cond: Value, todo: &mut Vec<State>) -> Option<bool> {
+        if let Expr::Const(v) = *cond {
+            return Some(v != 0);
+        }
+        if let Some(c) = s.constraints.iter().find(|c| c.expr == cond) {
+            return Some(c.nonzero);
+        }
+
+        let mut yes = s.clone();
+        yes.constrain(cond.clone(), true);
+        s.constrain(cond, false);
+        match (self.feasible(&yes), self.feasible(s)) {
+            (true, true) => {
+                todo.push(yes);
+                Some(false)
+            }
+            (true, false) => {
+                *s = yes;
+                Some(true)
+            }
+            (false, true) => Some(false),
+            (false, false) => None,
+        }
+    }
+
+    fn step(&self, s: &mut State, todo: &mut Vec<State>) -> Result<Next, ErrorSymbolic> {
+        let pc = s.pc;
+        let fault = |kind| Ok(Next::End(End::Fault { pc, kind }));
+
+        let word = match self.concrete(s, s.get(pc), todo)? {
+            Some(w) => w,
+            None => return Ok(Next::Dead),
+        };
+        let op = match Intcode::try_from(word) {
+            Ok(op) => op,
+            Err(ErrorIntcode::InvalidOpcode) => return fault(Fault::InvalidOpcode(word)),
+            Err(ErrorIntcode::InvalidMode) => return fault(Fault::InvalidMode(word)),
+        };
+        let modes = op.modes();
+        // Write parameters are left out
+        let reads = match op {
+            Intcode::Add(..) | Intcode::Mult(..) | Intcode::Lt(..) | Intcode::Equ(..) => 2,
+            Intcode::In(_) => 0,
+            _ => modes.len(),
+        };
+        let mut args = vec![];
+        for (i, &m) in modes[..reads].iter().enumerate() {
+            match s.read(i + 1, m) {
+                Ok(v) => args.push(v),
+                Err(kind) => return fault(kind),
+            }
+        }
+
+        let result = match op {
+            Intcode::Add(..) => Some(add(args[0].clone(), args[1].clone())),
+            Intcode::Mult(..) => Some(mul(args[0].clone(), args[1].clone())),
+            Intcode::Lt(..) => Some(lt(args[0].clone(), args[1].clone())),
+            Intcode::Equ(..) => Some(eq(args[0].clone(), args[1].clone())),
+            Intcode::In(_) => {
+                if s.fed < self.input.len() {
+                    Some(lit(self.input[s.fed]))
+                } else if self.input_domain.is_some() {
+                    Some(Rc::new(Expr::Sym(self.domains.len() + s.inputs)))
+                } else {
+                    return Ok(Next::End(End::NeedsInput));
+                }
+            }
+            _ => None,
+        };
+
+        if let Some(v) = result {
+            let i = op.size() - 1;
+            let addr = match s.param_addr(i, modes[i - 1]) {
+                Some(a) => a,
+                None => return fault(Fault::WriteToImmediate),
+            };
+            let addr = match self.concrete(s, addr, todo)? {
+                Some(a) => a,
+                None => return Ok(Next::Dead),
+            };
+            let addr = match to_addr(addr) {
+                Ok(a) => a,
+                Err(kind) => return fault(kind),
+            };
+            if let Err(kind) = s.set(addr, v) {
+                return fault(kind);
+            }
+            if let Intcode::In(_) = op {
+                if s.fed < self.input.len() {
+                    s.fed += 1;
+                } else {
+                    s.inputs += 1;
+                }
+            }
+            s.pc += op.size();
+            return Ok(Next::Continue);
+        }
+
+        match op {
+            Intcode::Out(_) => s.outputs.push(args[0].clone()),
+            Intcode::Jit(..) | Intcode::Jif(..) => {
+                let jit = matches!(op, Intcode::Jit(..));
+                let taken = match self.decide(s, args[0].clone(), todo) {
+                    Some(nonzero) => nonzero == jit,
+                    None => return Ok(Next::Dead),
+                };
+                if taken {
+                    let target = match self.concrete(s, args[1].clone(), todo)? {
+                        Some(t) => t,
+                        None => return Ok(Next::Dead),
+                    };
+                    match to_addr(target) {
+                        Ok(t) => s.pc = t,
+                        Err(kind) => return fault(kind),
+                    }
+                    return Ok(Next::Continue);
+                }
+            }
+            Intcode::Adj(_) => {
+                let v = match self.concrete(s, args[0].clone(), todo)? {
+                    Some(v) => v,
+                    None => return Ok(Next::Dead),
+                };
+                match s.rel.checked_add(v) {
+                    Some(rel) => s.rel = rel,
+                    None => return fault(Fault::Overflow),
+                }
+            }
+            Intcode::Halt => return Ok(Next::End(End::Halted)),
+            _ => unreachable!(),
+        }
+        s.pc += op.size();
+        Ok(Next::Continue)
+    }
+
+    /// Every path through the program
+    pub fn explore(&self) -> Result<Vec<Path>, ErrorSymbolic> {
+        let mut mem: Vec<Value> = self.mem.iter().map(|&v| lit(v)).collect();
+        for &(addr, s) in &self.cells {
+            if addr >= mem.len() {
+                mem.resize(addr + 1, lit(0));
+            }
+            mem[addr] = Rc::new(Expr::Sym(s));
+        }
+        let mut todo = vec![State {
+            mem: Rc::new(mem),
+            pc: 0,
+            rel: 0,
+            inputs: 0,
+            fed: 0,
+            outputs: vec![],
+            constraints: vec![],
+            steps: 0,
+        }];
+        let mut paths = vec![];
+
+        while let Some(mut s) = todo.pop() {
+            let end = loop {
+                if s.steps >= self.max_steps {
+                    break Some(End::StepLimit);
+                }
+                s.steps += 1;
+                match self.step(&mut s, &mut todo)? {
+                    Next::Continue => {}
+                    Next::End(end) => break Some(end),
+                    Next::Dead => break None,
+                }
+                if paths.len() + todo.len() >= self.max_paths {
+                    return Err(ErrorSymbolic::TooManyPaths);
+                }
+            };
+            if let Some(end) = end {
+                paths.push(Path {
+                    mem: s.mem.to_vec(),
+                    outputs: s.outputs,
+                    constraints: s.constraints,
+                    inputs: s.inputs,
+                    end,
+                });
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Symbol values that make `goal` equal `target` on a path that halts,
+    /// indexed like `symbol_at` and `symbolic_input` number them
+    pub fn solve(&self, goal: Goal, target: i64) -> Result<Option<Vec<i64>>, ErrorSymbolic> {
+        for path in self.explore()? {
+            if path.end != End::Halted {
+                continue;
+            }
+            let e = match goal {
+                Goal::Mem(a) => path.mem.get(a).cloned().unwrap_or_else(|| lit(0)),
+                Goal::Output(i) => match path.outputs.get(i) {
+                    Some(e) => e.clone(),
+                    None => continue,
+                },
+            };
+            if let Some(env) = self.solve_path(&path, &e, target)? {
+                return Ok(Some(env));
+            }
+        }
+        Ok(None)
+    }
+
+    fn solve_path(
+        &self,
+        path: &Path,
+        e: &Value,
+        target: i64,
+    ) -> Result<Option<Vec<i64>>, ErrorSymbolic> {
+        let mut vars = BTreeSet::new();
+        e.symbols(&mut vars);
+        Executor::constraint_symbols(&path.constraints, &mut vars);
+
+        let holds = |env: &[i64]| {
+            e.eval(env) == Some(target) && path.constraints.iter().all(|c| c.holds(env))
+        };
+        let mut env = self.env(path.inputs);
+
+        // Linear: solve for the widest symbol, enumerate the rest
+        let linear = e.linear().and_then(|(k, terms)| {
+            let x = *terms.keys().max_by_key(|&&s| width(&self.domain(s)))?;
+            Some((k, terms, x))
+        });
+        let found = match linear {
+            Some((k, terms, x)) => {
+                // The value `x` needs given the others, if it's in its domain
+                let domain = self.domain(x);
+                let solve_x = |env: &[i64]| {
+                    let mut rest = i128::from(target) - i128::from(k);
+                    for (&s, &c) in &terms {
+                        if s != x {
+                            rest -= i128::from(c) * i128::from(env[s]);
+                        }
+                    }
+                    let c = i128::from(terms[&x]);
+                    if rest % c != 0 {
+                        return None;
+                    }
+                    i64::try_from(rest / c).ok().filter(|v| domain.contains(v))
+                };
+
+                vars.remove(&x);
+                let vars: Vec<usize> = vars.into_iter().collect();
+                let found = self.enumerate(&vars, &mut env, MAX_ENUM, |env| match solve_x(env) {
+                    Some(v) => {
+                        let mut env = env.to_vec();
+                        env[x] = v;
+                        holds(&env)
+                    }
+                    None => false,
+                });
+                if found == Some(true) {
+                    env[x] = solve_x(&env).unwrap();
+                }
+                found
+            }
+            None => {
+                let vars: Vec<usize> = vars.into_iter().collect();
+                self.enumerate(&vars, &mut env, MAX_ENUM, holds)
+            }
+        };
+
+        match found {
+            Some(true) => Ok(Some(env)),
+            Some(false) => Ok(None),
+            None => Err(ErrorSymbolic::SearchTooLarge),
+        }
+    }
+}
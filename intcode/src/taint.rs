@@ -0,0 +1,235 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::transpile::Flow as Code;
+use crate::{Cpu, Intcode, Mode, Step};
+
+// Forward data flow: cells and input values get tags, and every value
+// computed from a tagged one carries the tag along. `ADD`, `MUL`, `LT` and
+// `EQ` give their result the tags of both operands, an `IN` gives the value
+// whatever `input` holds and `ARB` taints the relative base.
+//
+// A value read or written through a tagged pointer, or relative to a tagged
+// base, picks up the pointer's tags too, that's how table lookups and arrays
+// indexed by a tagged value are followed. Jumps don't propagate anything, so
+// values that only depend on a tag through a branch stay clean.
+
+pub type Tag = usize;
+
+/// Tags a value carries
+pub type Labels = BTreeSet<Tag>;
+
+/// Where a tagged value went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sink {
+    Mem(usize),
+    // Index among all the outputs since tracking started
+    Output(usize),
+}
+
+/// A tagged value stored or output by the instruction at `pc`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flow {
+    pub pc: usize,
+    pub to: Sink,
+    pub value: i64,
+    pub labels: Labels,
+}
+
+/// Tags on memory and the flows out of them, gathered while a cpu runs
+#[derive(Debug, Clone, Default)]
+pub struct Taint {
+    pub names: Vec<String>,
+    // Given to every value read by `IN`
+    pub input: Labels,
+    pub rel: Labels,
+    // Don't follow pointers, only the values themselves
+    pub values_only: bool,
+    pub flows: Vec<Flow>,
+    cells: HashMap<usize, Labels>,
+    outputs: usize,
+}
+
+// Tags of each parameter's value, and of the address it refers to
+type Params = Vec<(Labels, Labels)>;
+
+/// What an instruction can change, taken before it runs so it can be undone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mark {
+    cell: Option<(usize, Option<Labels>)>,
+    rel: Labels,
+    flows: usize,
+    outputs: usize,
+}
+
+impl Taint {
+    pub fn new_tag(&mut self, name: &str) -> Tag {
+        self.names.push(name.to_string());
+        self.names.len() - 1
+    }
+
+    /// Tag whatever value `addr` holds now
+    pub fn tag_cell(&mut self, addr: usize, tag: Tag) {
+        self.cells.entry(addr).or_default().insert(tag);
+    }
+
+    /// Give each of `cells` a tag of its own, named after the address. Which
+    /// cell an output came from is then in the output's labels.
+    pub fn tag_each(&mut self, cells: impl IntoIterator<Item = usize>) {
+        for addr in cells {
+            let tag = self.new_tag(&format!("[{}]", addr));
+            self.tag_cell(addr, tag);
+        }
+    }
+
+    /// `tag_each` on every cell outside the code `transpile::Flow` recovers,
+    /// returns how many were tagged
+    pub fn tag_data(&mut self, mem: &[i64]) -> usize {
+        let mut data = vec![];
+        let mut next = 0;
+        for (&pc, op) in &Code::recover(mem).code {
+            data.extend(next..pc);
+            next = next.max(pc + op.size());
+        }
+        data.extend(next..mem.len());
+        let n = data.len();
+        self.tag_each(data);
+        n
+    }
+
+    pub fn labels(&self, addr: usize) -> Labels {
+        self.cells.get(&addr).cloned().unwrap_or_default()
+    }
+
+    /// Cells holding a value derived from `tag`, lowest address first
+    pub fn cells(&self, tag: Tag) -> Vec<usize> {
+        let mut cells: Vec<usize> = self
+            .cells
+            .iter()
+            .filter(|(_, l)| l.contains(&tag))
+            .map(|(&a, _)| a)
+            .collect();
+        cells.sort_unstable();
+        cells
+    }
+
+    /// Outputs derived from `tag` as (index, value)
+    pub fn outputs(&self, tag: Tag) -> Vec<(usize, i64)> {
+        self.flows
+            .iter()
+            .filter_map(|f| match f.to {
+                Sink::Output(i) if f.labels.contains(&tag) => Some((i, f.value)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Tag names, `{a, b}`
+    pub fn describe(&self, labels: &Labels) -> String {
+        let names: Vec<&str> = labels.iter().map(|&t| self.names[t].as_str()).collect();
+        format!("{{{}}}", names.join(", "))
+    }
+
+    /// Mark to `revert` to, `write` is the cell the next instruction writes
+    pub fn mark(&self, write: Option<usize>) -> Mark {
+        Mark {
+            cell: write.map(|a| (a, self.cells.get(&a).cloned())),
+            rel: self.rel.clone(),
+            flows: self.flows.len(),
+            outputs: self.outputs,
+        }
+    }
+
+    /// Undo everything since `mark`, which has to be from the last
+    /// instruction
+    pub fn revert(&mut self, mark: Mark) {
+        match mark.cell {
+            Some((a, Some(labels))) => {
+                self.cells.insert(a, labels);
+            }
+            Some((a, None)) => {
+                self.cells.remove(&a);
+            }
+            None => {}
+        }
+        self.rel = mark.rel;
+        self.flows.truncate(mark.flows);
+        self.outputs = mark.outputs;
+    }
+
+    fn set(&mut self, pc: usize, addr: usize, value: i64, labels: Labels) {
+        if labels.is_empty() {
+            self.cells.remove(&addr);
+        } else {
+            self.cells.insert(addr, labels.clone());
+            self.flows.push(Flow {
+                pc,
+                to: Sink::Mem(addr),
+                value,
+                labels,
+            });
+        }
+    }
+
+    // Read before the instruction runs, it may overwrite its own operands
+    pub(crate) fn params(&self, cpu: &Cpu, op: Intcode) -> Params {
+        let mut params = vec![];
+        for (i, m) in op.modes().into_iter().enumerate() {
+            let at = cpu.pc + i + 1;
+            let ptr = match m {
+                _ if self.values_only => Labels::new(),
+                Mode::Pos => self.labels(at),
+                Mode::Im => Labels::new(),
+                Mode::Rel => &self.labels(at) | &self.rel,
+            };
+            // Faults are left to the instruction itself
+            let value = match cpu.param_addr(i + 1, m) {
+                Some(a) => &self.labels(a) | &ptr,
+                None => self.labels(at),
+            };
+            params.push((value, ptr));
+        }
+        params
+    }
+
+    /// Propagate through an instruction that ran, `write` is the cell it
+    /// wrote and the new value
+    pub(crate) fn record(
+        &mut self,
+        pc: usize,
+        op: Intcode,
+        params: &[(Labels, Labels)],
+        write: Option<(usize, i64)>,
+        step: Step,
+    ) {
+        match op {
+            Intcode::Add(..) | Intcode::Mult(..) | Intcode::Lt(..) | Intcode::Equ(..) => {
+                if let Some((a, v)) = write {
+                    let labels = &(&params[0].0 | &params[1].0) | &params[2].1;
+                    self.set(pc, a, v, labels);
+                }
+            }
+            Intcode::In(_) => {
+                if let Some((a, v)) = write {
+                    let labels = &self.input | &params[0].1;
+                    self.set(pc, a, v, labels);
+                }
+            }
+            Intcode::Out(_) => {
+                if let Step::Output(value) = step {
+                    let labels = params[0].0.clone();
+                    if !labels.is_empty() {
+                        self.flows.push(Flow {
+                            pc,
+                            to: Sink::Output(self.outputs),
+                            value,
+                            labels,
+                        });
+                    }
+                    self.outputs += 1;
+                }
+            }
+            Intcode::Adj(_) => self.rel.extend(&params[0].0),
+            Intcode::Jit(..) | Intcode::Jif(..) | Intcode::Halt => {}
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::asm::mnemonic_op;
+use crate::disasm::mnemonic;
+use crate::{Cpu, Intcode, Mode, Step};
+
+// One JSON object per line, fields that didn't happen are left out:
+//
+//     {"pc":0,"op":"ADD","modes":[1,1,0],"args":[1,2,3],"write":[3,3]}
+//     {"pc":4,"op":"IN","modes":[2],"args":[7],"write":[7,5],"in":5}
+//     {"pc":6,"op":"ARB","modes":[1],"args":[-2],"rel":-2}
+//
+// `args` are the operand values, the address for the operand that's written.
+// `rel` is the new relative base when the instruction changed it.
+
+/// A single executed instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub pc: usize,
+    pub op: Intcode,
+    pub args: Vec<i64>,
+    pub write: Option<(usize, i64)>,
+    pub rel: Option<i64>,
+    pub input: Option<i64>,
+    pub output: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorTrace {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for ErrorTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for ErrorTrace {}
+
+fn join(vals: &[i64]) -> String {
+    let vals: Vec<String> = vals.iter().map(|v| v.to_string()).collect();
+    vals.join(",")
+}
+
+impl Event {
+    pub fn to_json(&self) -> String {
+        let modes: Vec<i64> = self.op.modes().into_iter().map(i64::from).collect();
+        let mut out = format!(
+            "{{\"pc\":{},\"op\":\"{}\",\"modes\":[{}],\"args\":[{}]",
+            self.pc,
+            mnemonic(self.op),
+            join(&modes),
+            join(&self.args)
+        );
+        if let Some((a, v)) = self.write {
+            out.push_str(&format!(",\"write\":[{},{}]", a, v));
+        }
+        if let Some(r) = self.rel {
+            out.push_str(&format!(",\"rel\":{}", r));
+        }
+        if let Some(v) = self.input {
+            out.push_str(&format!(",\"in\":{}", v));
+        }
+        if let Some(v) = self.output {
+            out.push_str(&format!(",\"out\":{}", v));
+        }
+        out.push('}');
+        out
+    }
+
+    pub fn from_json(line: &str) -> Result<Event, String> {
+        let mut pc = None;
+        let mut name = None;
+        let mut modes = None;
+        let mut args = None;
+        let mut event = Event {
+            pc: 0,
+            op: Intcode::Halt,
+            args: vec![],
+            write: None,
+            rel: None,
+            input: None,
+            output: None,
+        };
+
+        for (key, val) in fields(line)? {
+            match (key, val) {
+                (_, Value::Null) => {}
+                ("pc", Value::Num(v)) => {
+                    pc = Some(usize::try_from(v).map_err(|_| format!("Bad pc {}", v))?)
+                }
+                ("op", Value::Str(s)) => name = Some(s),
+                ("modes", Value::List(l)) => modes = Some(l),
+                ("args", Value::List(l)) => args = Some(l),
+                ("write", Value::List(l)) => match l[..] {
+                    [a, v] if a >= 0 => event.write = Some((a as usize, v)),
+                    _ => return Err(format!("Bad write {:?}", l)),
+                },
+                ("rel", Value::Num(v)) => event.rel = Some(v),
+                ("in", Value::Num(v)) => event.input = Some(v),
+                ("out", Value::Num(v)) => event.output = Some(v),
+                (key, val) => return Err(format!("Unexpected `{}`: {:?}", key, val)),
+            }
+        }
+
+        event.pc = pc.ok_or("Missing pc")?;
+        let name = name.ok_or("Missing op")?;
+        let modes = modes
+            .ok_or("Missing modes")?
+            .into_iter()
+            .map(|m| Mode::try_from(m).map_err(|_| format!("Bad mode {}", m)))
+            .collect::<Result<Vec<Mode>, String>>()?;
+        event.op = mnemonic_op(name, &modes)
+            .ok_or_else(|| format!("Bad op `{}` with {} modes", name, modes.len()))?;
+        event.args = args.ok_or("Missing args")?;
+        Ok(event)
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_json())
+    }
+}
+
+#[derive(Debug)]
+enum Value<'a> {
+    Num(i64),
+    Str(&'a str),
+    List(Vec<i64>),
+    Null,
+}
+
+fn parse_num(s: &str) -> Result<i64, String> {
+    s.trim()
+        .parse()
+        .map_err(|_| format!("Bad number `{}`", s.trim()))
+}
+
+// Just enough JSON for the flat objects `to_json` writes
+fn fields(line: &str) -> Result<Vec<(&str, Value<'_>)>, String> {
+    let mut rest = line
+        .trim()
+        .strip_prefix('{')
+        .and_then(|s| s.strip_suffix('}'))
+        .ok_or("Expected an object")?
+        .trim();
+
+    let mut out = vec![];
+    while !rest.is_empty() {
+        let (key, after) = rest
+            .strip_prefix('"')
+            .and_then(|s| s.find('"').map(|end| (&s[..end], &s[end + 1..])))
+            .ok_or_else(|| format!("Expected a key at `{}`", rest))?;
+        rest = after.trim_start();
+        rest = rest
+            .strip_prefix(':')
+            .ok_or_else(|| format!("Expected `:` after `{}`", key))?
+            .trim_start();
+
+        let end = if rest.starts_with('[') {
+            rest.find(']').map(|i| i + 1)
+        } else if let Some(s) = rest.strip_prefix('"') {
+            s.find('"').map(|i| i + 2)
+        } else {
+            Some(rest.find(',').unwrap_or(rest.len()))
+        }
+        .ok_or_else(|| format!("Unterminated value for `{}`", key))?;
+
+        let raw = rest[..end].trim();
+        let val = if let Some(list) = raw.strip_prefix('[') {
+            let list = list[..list.len() - 1].trim();
+            if list.is_empty() {
+                Value::List(vec![])
+            } else {
+                Value::List(list.split(',').map(parse_num).collect::<Result<_, _>>()?)
+            }
+        } else if raw.starts_with('"') {
+            Value::Str(&raw[1..raw.len() - 1])
+        } else if raw == "null" {
+            Value::Null
+        } else {
+            Value::Num(parse_num(raw)?)
+        };
+        out.push((key, val));
+
+        rest = rest[end..].trim_start();
+        if let Some(r) = rest.strip_prefix(',') {
+            rest = r.trim_start();
+        } else if !rest.is_empty() {
+            return Err(format!("Expected `,` at `{}`", rest));
+        }
+    }
+    Ok(out)
+}
+
+/// Write a trace as JSON lines
+pub fn write(w: &mut impl Write, trace: &[Event]) -> io::Result<()> {
+    for event in trace {
+        writeln!(w, "{}", event.to_json())?;
+    }
+    Ok(())
+}
+
+/// Parse a trace written by `write`, blank lines are skipped
+pub fn read(text: &str) -> Result<Vec<Event>, ErrorTrace> {
+    let mut trace = vec![];
+    for (idx, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = Event::from_json(line).map_err(|msg| ErrorTrace { line: idx + 1, msg })?;
+        trace.push(event);
+    }
+    Ok(trace)
+}
+
+/// Index of the first event where two traces differ, or where the shorter
+/// one ends
+pub fn first_divergence(a: &[Event], b: &[Event]) -> Option<usize> {
+    match a.iter().zip(b).position(|(x, y)| x != y) {
+        Some(i) => Some(i),
+        None if a.len() != b.len() => Some(a.len().min(b.len())),
+        None => None,
+    }
+}
+
+/// Where a live run stopped agreeing with a recorded trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub index: usize,
+    pub expected: Event,
+    // `None` when the live cpu couldn't execute the instruction at all
+    pub actual: Option<Event>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Diverged at event {}", self.index)?;
+        writeln!(f, "expected: {}", self.expected)?;
+        match &self.actual {
+            Some(e) => write!(f, "actual:   {}", e),
+            None => write!(f, "actual:   nothing executed"),
+        }
+    }
+}
+
+/// Re-run `cpu` against a recorded trace, feeding it the recorded inputs.
+/// Returns how many events matched. The cpu's own trace setting is restored
+/// afterwards.
+pub fn replay(cpu: &mut Cpu, trace: &[Event]) -> Result<usize, Box<Divergence>> {
+    let saved = cpu.trace.replace(vec![]);
+    let mut result = Ok(trace.len());
+
+    for (index, expected) in trace.iter().enumerate() {
+        let mut feed = expected.input;
+        let actual = match cpu.step(&mut feed) {
+            Ok(Step::NeedsInput) | Err(_) => None,
+            Ok(_) => cpu.trace.as_mut().and_then(|t| t.pop()),
+        };
+        if actual.as_ref() != Some(expected) {
+            result = Err(Box::new(Divergence {
+                index,
+                expected: expected.clone(),
+                actual,
+            }));
+            break;
+        }
+    }
+
+    cpu.trace = saved;
+    result
+}
@@ -0,0 +1,688 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::disasm::disassemble_from;
+use crate::{Intcode, Mode};
+
+// Turns a program into a Rust module with a `Program` type that runs it
+// natively. Code is found by following control flow from address 0. Jumps
+// through memory (returns, mostly) can't be followed, so immediates that look
+// like pushed return addresses (`ADD #ret, #0, rb+0` and friends) are tried
+// as entry points too, and so are the entries of jump tables, where an `ADD`
+// computes `#base + i` straight into a jump's target operand. Every recovered
+// block is a `match` arm on pc, and a jump anywhere else hands over to the
+// interpreter.
+//
+// Self-modification is handled conservatively. Code words that fixed
+// address writes can reach are patched, and so are words the caller presets
+// before the program starts (a noun and verb, or a free play switch): operands
+// are read from memory instead of baked in, and an opcode that isn't the
+// original word any more runs that one instruction in the interpreter. Any
+// other write into code, through `rb` or from outside, hands over to the
+// interpreter before the modified code could run.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorTranspile {
+    NoCode,
+}
+
+impl fmt::Display for ErrorTranspile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorTranspile::NoCode => write!(f, "no instruction at address 0"),
+        }
+    }
+}
+
+impl std::error::Error for ErrorTranspile {}
+
+/// Instructions recovered from a program and where blocks start
+#[derive(Debug, Clone, Default)]
+pub struct Flow {
+    pub code: BTreeMap<usize, Intcode>,
+    pub starts: BTreeSet<usize>,
+}
+
+fn decode(mem: &[i64], addr: usize) -> Option<Intcode> {
+    let op = Intcode::try_from(*mem.get(addr)?).ok()?;
+    if addr + op.size() > mem.len() {
+        return None;
+    }
+    Some(op)
+}
+
+fn unconditional(op: Intcode, args: &[i64]) -> bool {
+    match op {
+        Intcode::Jit(Mode::Im, _) => args[0] != 0,
+        Intcode::Jif(Mode::Im, _) => args[0] == 0,
+        Intcode::Halt => true,
+        _ => false,
+    }
+}
+
+impl Flow {
+    fn covers(&self, addr: usize) -> bool {
+        match self.code.range(..=addr).next_back() {
+            Some((&a, op)) => addr < a + op.size(),
+            None => false,
+        }
+    }
+
+    fn is_start_of(&self, addr: usize) -> bool {
+        self.code.contains_key(&addr)
+    }
+
+    /// Addresses occupied by recovered instructions, as inclusive ranges
+    pub fn code_ranges(&self) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = vec![];
+        for (&a, op) in &self.code {
+            let end = a + op.size() - 1;
+            match ranges.last_mut() {
+                Some(r) if r.1 + 1 >= a => r.1 = r.1.max(end),
+                _ => ranges.push((a, end)),
+            }
+        }
+        ranges
+    }
+
+    // Straight line from `start` until something that doesn't fall through.
+    // Speculative walks are dropped whole if they hit garbage or run into the
+    // middle of known code.
+    fn walk(&mut self, mem: &[i64], start: usize, sure: bool) -> Vec<usize> {
+        let mut found = vec![];
+        let mut targets = vec![];
+        let mut pc = start;
+
+        loop {
+            if self.is_start_of(pc) {
+                // Ran into known code, which now needs a block of its own
+                if pc != start {
+                    self.starts.insert(pc);
+                }
+                break;
+            }
+            let op = match decode(mem, pc) {
+                Some(op) if sure || !(pc..pc + op.size()).any(|a| self.covers(a)) => op,
+                Some(_) | None if !sure => return vec![],
+                _ => break,
+            };
+            let args = &mem[pc + 1..pc + op.size()];
+            found.push((pc, op));
+
+            match op {
+                Intcode::Jit(_, Mode::Im) | Intcode::Jif(_, Mode::Im) if args[1] >= 0 => {
+                    targets.push(args[1] as usize)
+                }
+                Intcode::Out(_) => targets.push(pc + op.size()),
+                _ => {}
+            }
+            if unconditional(op, args) || pc + op.size() >= mem.len() {
+                break;
+            }
+            pc += op.size();
+        }
+
+        if found.is_empty() {
+            return vec![];
+        }
+        self.starts.insert(start);
+        for (pc, op) in found {
+            if let Intcode::In(_) = op {
+                self.starts.insert(pc);
+            }
+            self.code.insert(pc, op);
+        }
+        targets
+    }
+
+    // Bases of jump tables, `ADD x, #base, [t]` where `t` is the target
+    // operand of a jump through memory
+    fn tables(&self, mem: &[i64]) -> Vec<usize> {
+        let operands: BTreeSet<usize> = self
+            .code
+            .iter()
+            .filter_map(|(&pc, &op)| match op {
+                Intcode::Jit(_, Mode::Pos) | Intcode::Jif(_, Mode::Pos) => Some(pc + 2),
+                _ => None,
+            })
+            .collect();
+        self.code
+            .iter()
+            .filter_map(|(&pc, &op)| {
+                let base = match op {
+                    Intcode::Add(_, Mode::Im, Mode::Pos) => mem[pc + 2],
+                    Intcode::Add(Mode::Im, _, Mode::Pos) => mem[pc + 1],
+                    _ => return None,
+                };
+                let t = usize::try_from(mem[pc + 3]).ok()?;
+                if operands.contains(&t) {
+                    usize::try_from(base).ok()
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // Walk the entries of the table at `base`, it ends at the first word
+    // that doesn't lead to code
+    fn table(&mut self, mem: &[i64], base: usize) -> Vec<usize> {
+        let mut targets = vec![];
+        for &w in mem.get(base..).unwrap_or(&[]) {
+            let a = match usize::try_from(w) {
+                Ok(a) if a < mem.len() => a,
+                _ => break,
+            };
+            if !self.is_start_of(a) {
+                if self.covers(a) {
+                    break;
+                }
+                targets.extend(self.walk(mem, a, false));
+                if !self.is_start_of(a) {
+                    break;
+                }
+            }
+            self.starts.insert(a);
+        }
+        targets
+    }
+
+    /// Follow control flow from address 0
+    pub fn recover(mem: &[i64]) -> Flow {
+        let mut flow = Flow::default();
+        let mut sure = vec![0];
+        let mut done = BTreeSet::new();
+        let mut tables = BTreeSet::new();
+
+        loop {
+            while let Some(a) = sure.pop() {
+                if a < mem.len() && done.insert(a) {
+                    let targets = flow.walk(mem, a, true);
+                    sure.extend(targets);
+                }
+                if flow.is_start_of(a) {
+                    flow.starts.insert(a);
+                }
+            }
+
+            // Candidate return addresses pushed by known code
+            let guesses: Vec<usize> = flow
+                .code
+                .iter()
+                .filter_map(|(&pc, &op)| match (op, mem.get(pc + 1..pc + 3)) {
+                    (Intcode::Add(Mode::Im, Mode::Im, _), Some(&[a, 0]))
+                    | (Intcode::Add(Mode::Im, Mode::Im, _), Some(&[0, a]))
+                    | (Intcode::Mult(Mode::Im, Mode::Im, _), Some(&[a, 1]))
+                    | (Intcode::Mult(Mode::Im, Mode::Im, _), Some(&[1, a])) => {
+                        usize::try_from(a).ok()
+                    }
+                    _ => None,
+                })
+                .filter(|&a| a < mem.len() && !done.contains(&a))
+                .collect();
+            let bases: Vec<usize> = flow
+                .tables(mem)
+                .into_iter()
+                .filter(|&b| tables.insert(b))
+                .collect();
+            if guesses.is_empty() && bases.is_empty() {
+                break;
+            }
+            for base in bases {
+                sure.extend(flow.table(mem, base));
+            }
+            for a in guesses {
+                done.insert(a);
+                if flow.is_start_of(a) {
+                    flow.starts.insert(a);
+                } else if !flow.covers(a) {
+                    sure.extend(flow.walk(mem, a, false));
+                }
+            }
+        }
+        flow
+    }
+
+    /// Code words that instructions with a fixed write address can change,
+    /// plus those in `preset`, which the caller may write before the start
+    pub fn patches(&self, mem: &[i64], preset: &[usize]) -> BTreeSet<usize> {
+        let mut patched: BTreeSet<usize> =
+            preset.iter().copied().filter(|&a| self.covers(a)).collect();
+        for (&pc, &op) in &self.code {
+            let n = match op {
+                Intcode::Add(_, _, Mode::Pos)
+                | Intcode::Mult(_, _, Mode::Pos)
+                | Intcode::Lt(_, _, Mode::Pos)
+                | Intcode::Equ(_, _, Mode::Pos) => 3,
+                Intcode::In(Mode::Pos) => 1,
+                _ => continue,
+            };
+            let addr = match usize::try_from(mem[pc + n]) {
+                Ok(a) => a,
+                Err(_) => continue,
+            };
+            if self.covers(addr) {
+                patched.insert(addr);
+            }
+        }
+        patched
+    }
+}
+
+const PRELUDE: &str = r#"use intcode::{Cpu, Fault, Memory, Status, Step};
+
+#[allow(unused_macros)]
+macro_rules! at {
+    ($self:ident, $pc:expr, $e:expr) => {
+        match $e {
+            Ok(v) => v,
+            Err(kind) => {
+                $self.pc = $pc;
+                return Status::Fault { pc: $pc, kind };
+            }
+        }
+    };
+}
+
+// Same, but an `In` hands its input back when it faults
+#[allow(unused_macros)]
+macro_rules! at_in {
+    ($self:ident, $pc:expr, $input:ident, $v:ident, $e:expr) => {
+        match $e {
+            Ok(v) => v,
+            Err(kind) => {
+                *$input = Some($v);
+                $self.pc = $pc;
+                return Status::Fault { pc: $pc, kind };
+            }
+        }
+    };
+}
+
+#[allow(dead_code)]
+fn addr(v: i64) -> Result<usize, Fault> {
+    if v < 0 {
+        Err(Fault::NegativeAddress(v))
+    } else {
+        Ok(v as usize)
+    }
+}
+"#;
+
+const METHODS: &str = r#"
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub mem: Memory,
+    pub pc: usize,
+    pub rel: i64,
+    // Set once execution had to hand over to the interpreter
+    fallback: Option<Cpu>,
+}
+
+#[allow(clippy::new_without_default)]
+impl Program {
+    pub fn new() -> Program {
+        let mut mem = Memory::new(PROG.to_vec());
+        mem.track_writes(true);
+        Program {
+            mem,
+            pc: 0,
+            rel: 0,
+            fallback: None,
+        }
+    }
+
+    /// Whether execution has handed over to the interpreter
+    pub fn fell_back(&self) -> bool {
+        self.fallback.is_some()
+    }
+
+    /// Where execution handed over to the interpreter, if it did
+    pub fn fell_back_at(&self) -> Option<usize> {
+        // `pc` stops moving once the interpreter takes over
+        self.fallback.as_ref().map(|_| self.pc)
+    }
+
+    /// Memory, whichever side is running the program
+    pub fn memory(&self) -> &Memory {
+        match &self.fallback {
+            Some(cpu) => &cpu.mem,
+            None => &self.mem,
+        }
+    }
+
+    #[allow(dead_code)]
+    fn ld(&self, a: i64) -> Result<i64, Fault> {
+        Ok(self.mem.get(addr(a)?))
+    }
+
+    #[allow(dead_code)]
+    fn rb(&self, off: i64) -> Result<usize, Fault> {
+        addr(off.checked_add(self.rel).ok_or(Fault::Overflow)?)
+    }
+
+    #[allow(dead_code)]
+    fn st(&mut self, a: usize, v: i64) -> Result<(), Fault> {
+        Ok(self.mem.set(a, v)?)
+    }
+
+    // Run the instruction at `pc` in the interpreter, for an opcode that
+    // isn't the one baked in. `None` means native code carries on from the
+    // new pc.
+    #[allow(dead_code)]
+    fn interp_step(&mut self, input: &mut Option<i64>) -> Option<Status> {
+        let mut cpu = Cpu::with_mem(vec![]);
+        cpu.mem = std::mem::take(&mut self.mem);
+        cpu.pc = self.pc;
+        cpu.rel = self.rel;
+        let step = cpu.step(input);
+        self.mem = std::mem::take(&mut cpu.mem);
+        self.pc = cpu.pc;
+        self.rel = cpu.rel;
+        match step {
+            Ok(Step::Continue) => {}
+            Ok(Step::Output(v)) => return Some(Status::Output(v)),
+            Ok(Step::NeedsInput) => return Some(Status::NeedsInput),
+            Ok(Step::Halted) => return Some(Status::Halted),
+            Err(kind) => return Some(Status::Fault { pc: self.pc, kind }),
+        }
+        let mut touched = false;
+        self.mem.drain_writes(|a| touched |= is_code(a));
+        if touched {
+            return Some(self.fall_back(input));
+        }
+        None
+    }
+
+    fn fall_back(&mut self, input: &mut Option<i64>) -> Status {
+        let mut cpu = Cpu::with_mem(vec![]);
+        cpu.mem = std::mem::take(&mut self.mem);
+        cpu.mem.track_writes(false);
+        cpu.pc = self.pc;
+        cpu.rel = self.rel;
+        let status = cpu.run(input);
+        self.fallback = Some(cpu);
+        status
+    }
+
+    /// Run until the next output, halt, fault, or an `In` while `input` is empty
+    #[allow(clippy::never_loop)]
+    pub fn run(&mut self, input: &mut Option<i64>) -> Status {
+        if let Some(cpu) = self.fallback.as_mut() {
+            return cpu.run(input);
+        }
+        // Code baked in below can't change, so if anything outside wrote to
+        // it, or swapped the memory, only the interpreter can carry on
+        let mut touched = !self.mem.tracking_writes();
+        self.mem.drain_writes(|a| touched |= is_code(a));
+        if touched {
+            return self.fall_back(input);
+        }
+
+        loop {
+            match self.pc {
+"#;
+
+const INDENT: &str = "                    ";
+
+struct Gen<'a> {
+    mem: &'a [i64],
+    flow: &'a Flow,
+    patched: BTreeSet<usize>,
+    out: String,
+}
+
+impl<'a> Gen<'a> {
+    fn line(&mut self, s: &str) {
+        self.out.push_str(INDENT);
+        self.out.push_str(s);
+        self.out.push('\n');
+    }
+
+    // The word at `addr`, as a literal unless code patches it
+    fn word(&self, addr: usize) -> String {
+        if self.patched.contains(&addr) {
+            format!("self.mem.get({})", addr)
+        } else {
+            format!("{}i64", self.mem[addr])
+        }
+    }
+
+    // Expression reading parameter `i` of the instruction at `pc`
+    fn load(&self, pc: usize, m: Mode, i: usize) -> String {
+        let addr = pc + i + 1;
+        let w = self.mem[addr];
+        match m {
+            Mode::Im => self.word(addr),
+            _ if self.patched.contains(&addr) && m == Mode::Pos => {
+                format!("at!(self, {}, self.ld({}))", pc, self.word(addr))
+            }
+            Mode::Pos if w >= 0 => format!("self.mem.get({})", w),
+            Mode::Pos => format!("at!(self, {}, self.ld({}))", pc, w),
+            Mode::Rel => format!(
+                "self.mem.get(at!(self, {}, self.rb({})))",
+                pc,
+                self.word(addr)
+            ),
+        }
+    }
+
+    // Statements writing `v` through parameter `i`, an `In` needs its input
+    // restored on the way out
+    fn store(&mut self, pc: usize, m: Mode, i: usize, is_in: bool) {
+        let at = |e: &str| {
+            if is_in {
+                format!("at_in!(self, {}, input, v, {})", pc, e)
+            } else {
+                format!("at!(self, {}, {})", pc, e)
+            }
+        };
+        let addr = pc + i + 1;
+        let w = self.mem[addr];
+        let target = match m {
+            Mode::Im => {
+                let s = at("Err::<(), _>(Fault::WriteToImmediate)");
+                return self.line(&format!("{};", s));
+            }
+            Mode::Pos if !self.patched.contains(&addr) => {
+                let s = if w >= 0 {
+                    at(&format!("self.st({}, v)", w))
+                } else {
+                    at(&format!("Err::<(), _>(Fault::NegativeAddress({}))", w))
+                };
+                return self.line(&format!("{};", s));
+            }
+            Mode::Pos => format!("addr({})", self.word(addr)),
+            Mode::Rel => format!("self.rb({})", self.word(addr)),
+        };
+
+        let restore = if is_in { "*input = Some(v); " } else { "" };
+        self.line(&format!("let d = {};", at(&target)));
+        self.line(&format!(
+            "if is_code(d) {{ {}self.pc = {}; return self.fall_back(input); }}",
+            restore, pc
+        ));
+        self.line(&format!("{};", at("self.st(d, v)")));
+    }
+
+    // Whether a jump is always taken, patched conditions never count
+    fn unconditional(&self, pc: usize, op: Intcode) -> bool {
+        !self.patched.contains(&(pc + 1)) && unconditional(op, &self.mem[pc + 1..pc + op.size()])
+    }
+
+    fn op(&mut self, pc: usize, op: Intcode) {
+        let modes = op.modes();
+        let arg = |g: &Gen, i: usize| g.load(pc, modes[i], i);
+        let next = pc + op.size();
+
+        if let Some(l) = disassemble_from(self.mem, pc, 1).first() {
+            self.line(&format!("// {:>5}: {}", pc, l.text()));
+        }
+        if self.patched.contains(&pc) {
+            self.line(&format!(
+                "if self.mem.get({}) != {} {{ self.pc = {}; match self.interp_step(input) {{ Some(s) => return s, None => continue }} }}",
+                pc, self.mem[pc], pc
+            ));
+        }
+        match op {
+            Intcode::Add(..) | Intcode::Mult(..) => {
+                let f = match op {
+                    Intcode::Add(..) => "checked_add",
+                    _ => "checked_mul",
+                };
+                self.line(&format!("let a = {};", arg(self, 0)));
+                self.line(&format!("let b = {};", arg(self, 1)));
+                self.line(&format!(
+                    "let v = at!(self, {}, a.{}(b).ok_or(Fault::Overflow));",
+                    pc, f
+                ));
+                self.store(pc, modes[2], 2, false);
+            }
+            Intcode::Lt(..) | Intcode::Equ(..) => {
+                let cmp = match op {
+                    Intcode::Lt(..) => "<",
+                    _ => "==",
+                };
+                self.line(&format!("let a = {};", arg(self, 0)));
+                self.line(&format!("let b = {};", arg(self, 1)));
+                self.line(&format!("let v = (a {} b) as i64;", cmp));
+                self.store(pc, modes[2], 2, false);
+            }
+            Intcode::In(m) => {
+                self.line(&format!(
+                    "let v = match input.take() {{ Some(v) => v, None => {{ self.pc = {}; return Status::NeedsInput; }} }};",
+                    pc
+                ));
+                self.store(pc, m, 0, true);
+            }
+            Intcode::Out(_) => {
+                self.line(&format!("let a = {};", arg(self, 0)));
+                self.line(&format!("self.pc = {};", next));
+                self.line("return Status::Output(a);");
+            }
+            Intcode::Jit(..) | Intcode::Jif(..) if self.unconditional(pc, op) => {
+                self.line(&format!("let b = {};", arg(self, 1)));
+                self.line(&format!("self.pc = at!(self, {}, addr(b));", pc));
+                self.line("continue;");
+            }
+            Intcode::Jit(..) | Intcode::Jif(..) => {
+                let cmp = match op {
+                    Intcode::Jit(..) => "!=",
+                    _ => "==",
+                };
+                self.line(&format!("let a = {};", arg(self, 0)));
+                self.line(&format!("let b = {};", arg(self, 1)));
+                self.line(&format!(
+                    "if a {} 0 {{ self.pc = at!(self, {}, addr(b)); continue; }}",
+                    cmp, pc
+                ));
+            }
+            Intcode::Adj(_) => {
+                self.line(&format!("let a = {};", arg(self, 0)));
+                self.line(&format!(
+                    "self.rel = at!(self, {}, self.rel.checked_add(a).ok_or(Fault::Overflow));",
+                    pc
+                ));
+            }
+            Intcode::Halt => {
+                self.line(&format!("self.pc = {};", pc));
+                self.line("return Status::Halted;");
+            }
+        }
+    }
+
+    fn block(&mut self, start: usize) {
+        self.out
+            .push_str(&format!("                {} => {{\n", start));
+        let mut pc = start;
+        loop {
+            let op = self.flow.code[&pc];
+            self.op(pc, op);
+            if let Intcode::Out(_) | Intcode::Halt = op {
+                break;
+            }
+            if self.unconditional(pc, op) {
+                break;
+            }
+            pc += op.size();
+            if self.flow.starts.contains(&pc) || !self.flow.code.contains_key(&pc) {
+                self.line(&format!("self.pc = {};", pc));
+                break;
+            }
+        }
+        self.out.push_str("                }\n");
+    }
+}
+
+/// Rust source for a module running `mem` natively. `preset` are addresses
+/// the caller may write before the program starts.
+pub fn transpile(mem: &[i64], preset: &[usize]) -> Result<String, ErrorTranspile> {
+    let mut flow = Flow::recover(mem);
+    if flow.code.is_empty() {
+        return Err(ErrorTranspile::NoCode);
+    }
+    let patched = flow.patches(mem, preset);
+    // Instructions whose opcode can change get blocks of their own, and so
+    // does what follows, for native code to pick up from
+    for &a in &patched {
+        if let Some(op) = flow.code.get(&a) {
+            let next = a + op.size();
+            flow.starts.insert(a);
+            if flow.code.contains_key(&next) {
+                flow.starts.insert(next);
+            }
+        }
+    }
+
+    let mut out = String::from("// Generated by `intcode transpile`, do not edit\n\n");
+    out.push_str(PRELUDE);
+
+    let words: Vec<String> = mem.iter().map(|w| w.to_string()).collect();
+    out.push_str(&format!(
+        "\npub static PROG: [i64; {}] = [{}];\n",
+        mem.len(),
+        words.join(", ")
+    ));
+
+    // Words baked into the generated code
+    let mut baked: Vec<String> = vec![];
+    for (a, b) in flow.code_ranges() {
+        let mut from = a;
+        for p in patched.range(a..=b).copied().chain(Some(b + 1)) {
+            if from < p {
+                baked.push(format!("{}..={}", from, p - 1));
+            }
+            from = p + 1;
+        }
+    }
+    // Nothing baked in when every code word is patched
+    if baked.is_empty() {
+        out.push_str("\nfn is_code(_: usize) -> bool {\n    false\n}\n");
+    } else {
+        out.push_str(&format!(
+            "\nfn is_code(a: usize) -> bool {{\n    matches!(a, {})\n}}\n",
+            baked.join(" | ")
+        ));
+    }
+    out.push_str(METHODS);
+
+    let mut gen = Gen {
+        mem,
+        flow: &flow,
+        patched,
+        out,
+    };
+    for &start in &flow.starts {
+        gen.block(start);
+    }
+    gen.out.push_str(
+        "                _ => return self.fall_back(input),
+            }
+        }
+    }
+}
+",
+    );
+    Ok(gen.out)
+}
@@ -0,0 +1,104 @@
+use intcode::ascii::{Ascii, ErrorAscii, Invalid};
+use intcode::{asm, parse_mem, Cpu, Limit, Limits};
+
+// IN [9]; OUT [9]; JIT #1, #0
+const ECHO: &str = "3,9,4,9,1105,1,0,99,0,0";
+
+// Asks for a name and echoes it, then prints a number, a byte that isn't
+// ASCII and some more text
+const GREET: &str = r#"
+        ARB #msg
+print:  JIF rb+0, #read
+        OUT rb+0
+        ARB #1
+        JIT #1, #print
+read:   IN  [c]
+        OUT [c]
+        EQ  [c], #'\n', [t]
+        JIF [t], #read
+        OUT #1000
+        OUT #200
+        OUT #'!'
+        HLT
+c:      .data 0
+t:      .data 0
+msg:    .data "Hi\nName? ", 0
+"#;
+
+fn greet() -> Ascii {
+    Ascii::new(Cpu::with_mem(asm::assemble(GREET).unwrap()))
+}
+
+#[test]
+fn prompts_and_numbers() {
+    let mut ascii = greet();
+    assert_eq!(ascii.read_until_prompt("Hi\n").unwrap(), "Hi\n");
+    assert_eq!(ascii.read_until_prompt("? ").unwrap(), "Name? ");
+    // Waiting for input that isn't there
+    assert_eq!(ascii.read_until_prompt("? "), Err(ErrorAscii::NoPrompt));
+    assert_eq!(ascii.send_line("Zoë"), Err(ErrorAscii::NotAscii('ë')));
+
+    ascii.send_line("Bob").unwrap();
+    assert_eq!(ascii.read_all_text().unwrap(), "Bob\n!");
+    assert_eq!(ascii.numbers, [1000, 200]);
+    assert!(ascii.halted());
+    assert_eq!(ascii.read_all_text().unwrap(), "");
+}
+
+#[test]
+fn invalid_bytes() {
+    let run = |invalid| {
+        let mut ascii = greet();
+        ascii.invalid = invalid;
+        ascii.send_line("Bob").unwrap();
+        let text = ascii.read_all_text();
+        (text, Vec::from(ascii.numbers))
+    };
+    assert_eq!(
+        run(Invalid::Latin1),
+        (Ok("Hi\nName? Bob\n\u{c8}!".to_string()), vec![1000])
+    );
+    assert_eq!(
+        run(Invalid::Replace),
+        (Ok("Hi\nName? Bob\n\u{fffd}!".to_string()), vec![1000])
+    );
+    assert_eq!(
+        run(Invalid::Error),
+        (Err(ErrorAscii::InvalidByte(200)), vec![1000])
+    );
+}
+
+#[test]
+fn input_survives_a_limit() {
+    let mut cpu = Cpu::with_mem(parse_mem(ECHO));
+    cpu.set_limits(Limits {
+        instructions: Some(3),
+        ..Limits::default()
+    });
+    let mut ascii = Ascii::new(cpu);
+    ascii.send_line("ab").unwrap();
+    assert_eq!(
+        ascii.read_all_text(),
+        Err(ErrorAscii::LimitExceeded {
+            pc: 0,
+            limit: Limit::Instructions
+        })
+    );
+
+    ascii.cpu.clear_limits();
+    assert_eq!(ascii.read_all_text().unwrap(), "ab\n");
+}
+
+#[test]
+fn input_survives_a_fault() {
+    // ARB #-20; IN rb+0, which faults until rel is fixed up; OUT rb+0; HLT
+    let mut ascii = Ascii::new(Cpu::with_mem(parse_mem("109,-20,203,0,204,0,99")));
+    ascii.send_line("a").unwrap();
+    assert!(matches!(
+        ascii.read_all_text(),
+        Err(ErrorAscii::Fault { pc: 2, .. })
+    ));
+
+    ascii.cpu.rel = 30;
+    assert_eq!(ascii.read_all_text().unwrap(), "a");
+}
@@ -0,0 +1,74 @@
+use intcode::asm::{assemble, ErrorAsm};
+use intcode::disasm::{disassemble, listing};
+use intcode::{Cpu, Status};
+
+// Print the message, then count down from [n]
+const SRC: &str = r#"
+        ARB #msg
+print:  JIF rb+0, #count        ; up to the 0 after the text
+        OUT rb+0
+        ARB #1
+        JIT #1, #print
+count:  OUT [n]
+        ADD [n], #-1, [n]
+        JIT [n], #count
+        HLT
+n:      .data 3
+msg:    .data "Hi\n", 0
+end:    .data end-msg
+"#;
+
+fn run(mem: &[i64]) -> Vec<i64> {
+    let mut cpu = Cpu::with_mem(mem.to_vec());
+    let mut out = vec![];
+    loop {
+        match cpu.run(&mut None) {
+            Status::Output(v) => out.push(v),
+            s => {
+                assert_eq!(s, Status::Halted);
+                return out;
+            }
+        }
+    }
+}
+
+#[test]
+fn labelled_program() {
+    let mem = assemble(SRC).unwrap();
+    assert_eq!(
+        mem,
+        [
+            109, 23, 1206, 0, 12, 204, 0, 109, 1, 1105, 1, 2, 4, 22, 1001, 22, -1, 22, 1005, 22,
+            12, 99, 3, 72, 105, 10, 0, 4
+        ][..]
+    );
+    assert_eq!(run(&mem), [72, 105, 10, 3, 2, 1]);
+}
+
+#[test]
+fn disassembly_assembles_back() {
+    let mem = assemble(SRC).unwrap();
+    assert_eq!(assemble(&listing(&mem)).unwrap(), mem);
+
+    // Just the instructions, without the address and raw words
+    let text: Vec<String> = disassemble(&mem).iter().map(|l| l.text()).collect();
+    assert_eq!(text[..3], ["ARB #23", "JIF rb+0, #12", "OUT rb+0"]);
+    assert_eq!(assemble(&text.join("\n")).unwrap(), mem);
+
+    // The extremes of a word
+    let mem = vec![109, i64::MIN, i64::MIN, i64::MAX, 99];
+    assert_eq!(assemble(&listing(&mem)).unwrap(), mem);
+}
+
+#[test]
+fn errors() {
+    let line = |src: &str| assemble(src).map_err(|ErrorAsm { line, .. }| line);
+    assert_eq!(line("HLT\nJIT #1, #nowhere"), Err(2));
+    assert_eq!(line("a: HLT\na: HLT"), Err(2));
+    assert_eq!(line("\n\nADD #1, #2"), Err(3));
+    assert_eq!(line("OUT 5"), Err(1));
+    assert_eq!(line(".data \"\\q\""), Err(1));
+    assert_eq!(line("HLT\n.data 9223372036854775807+1"), Err(2));
+    assert_eq!(line(".data -9223372036854775808-1"), Err(1));
+    assert_eq!(line(".data 9223372036854775808"), Err(1));
+}
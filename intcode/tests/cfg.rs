@@ -0,0 +1,78 @@
+use intcode::cfg::{Cfg, Exit};
+use intcode::{asm, Cpu, Status};
+
+// Calls a function the way compiled Intcode does: push the return address,
+// jump, and return through `rb` after dropping the frame
+const CALL: &str = "
+        ARB #stack
+        ADD #ret, #0, rb+0
+        JIT #1, #double
+ret:    OUT [x]
+        HLT
+double: ARB #2
+        MUL [x], #2, [x]
+        JIF [x], #skip
+        ADD [x], #0, [x]
+skip:   ARB #-2
+        JIF #0, rb+0
+x:      .data 21
+stack:  .data 0, 0, 0, 0
+";
+
+#[test]
+fn blocks_and_functions() {
+    let mem = asm::assemble(CALL).unwrap();
+    let mut cpu = Cpu::with_mem(mem.clone());
+    assert_eq!(cpu.run(&mut None), Status::Output(42));
+
+    let cfg = Cfg::recover(&mem);
+    let exits: Vec<(usize, Exit)> = cfg.blocks.values().map(|b| (b.start, b.exit)).collect();
+    assert_eq!(
+        exits,
+        [
+            (0, Exit::Call { target: 12, ret: 9 }),
+            (9, Exit::Halt),
+            (
+                12,
+                Exit::Branch {
+                    taken: 25,
+                    fall: 21
+                }
+            ),
+            (21, Exit::Fall(25)),
+            (25, Exit::Return(None)),
+        ]
+    );
+    assert_eq!(cfg.blocks[&0].ops.len(), 3);
+    assert_eq!(cfg.blocks[&12].end(), 21);
+
+    let entries: Vec<usize> = cfg.functions.keys().copied().collect();
+    assert_eq!(entries, [0, 12]);
+    let main = &cfg.functions[&0];
+    assert_eq!(main.blocks.iter().copied().collect::<Vec<_>>(), [0, 9]);
+    assert_eq!(main.calls.iter().copied().collect::<Vec<_>>(), [12]);
+    let double = &cfg.functions[&12];
+    assert_eq!(double.frame, Some(2));
+    assert!(double.calls.is_empty());
+    assert_eq!(cfg.function_of(21).map(|f| f.entry), Some(12));
+    assert_eq!(cfg.function_of(30), None);
+}
+
+#[test]
+fn dot() {
+    let mem = asm::assemble(CALL).unwrap();
+    let cfg = Cfg::recover(&mem);
+
+    let dot = cfg.to_dot(&mem);
+    assert!(dot.starts_with("digraph cfg {\n"));
+    assert!(dot.contains("    subgraph cluster_12 {\n        label=\"fn @12\";\n"));
+    assert!(dot.contains("    b0 -> b12 [style=bold color=red];\n"));
+    assert!(dot.contains("    b0 -> b9 [style=dashed];\n"));
+    assert!(dot.contains("    b12 -> b25 [color=blue];\n"));
+    assert!(dot.contains("    b21 -> b25;\n"));
+    assert!(dot.contains("   12: ARB #2\\l"));
+
+    let calls = cfg.call_graph_dot();
+    assert!(calls.contains("    f12 [label=\"fn @12\\n3 blocks\\nframe 2\"];\n"));
+    assert!(calls.contains("    f0 -> f12;\n"));
+}
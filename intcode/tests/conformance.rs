@@ -0,0 +1,222 @@
+use intcode::io::Iter;
+use intcode::{parse_mem, Cpu, Engine, Fault, Status};
+
+// The examples from days 02, 05 and 09, plus the corners they don't reach.
+// Everything runs on both engines, which have to end up in the same place.
+
+const ENGINES: [Engine; 2] = [Engine::Interp, Engine::Cached];
+
+struct Run {
+    cpu: Cpu,
+    outputs: Vec<i64>,
+    status: Status,
+}
+
+fn run(prog: &str, input: &[i64]) -> Run {
+    let mut runs = ENGINES.iter().map(|&engine| {
+        let mut cpu = Cpu::with_engine(parse_mem(prog), engine);
+        let mut outputs = vec![];
+        let status = cpu.run_io(Iter(input.iter().copied()), &mut outputs);
+        Run {
+            cpu,
+            outputs,
+            status,
+        }
+    });
+
+    let first = runs.next().unwrap();
+    for other in runs {
+        assert_eq!(first.outputs, other.outputs, "{}", prog);
+        assert_eq!(first.status, other.status, "{}", prog);
+        assert_eq!(
+            first.cpu.mem.as_slice(),
+            other.cpu.mem.as_slice(),
+            "{}",
+            prog
+        );
+        assert_eq!(first.cpu.mem.sparse(), other.cpu.mem.sparse(), "{}", prog);
+    }
+    first
+}
+
+fn outputs(prog: &str, input: &[i64]) -> Vec<i64> {
+    let r = run(prog, input);
+    assert_eq!(r.status, Status::Halted, "{}", prog);
+    r.outputs
+}
+
+fn fault(prog: &str, input: &[i64]) -> (usize, Fault) {
+    match run(prog, input).status {
+        Status::Fault { pc, kind } => (pc, kind),
+        s => panic!("{}: expected a fault, got {:?}", prog, s),
+    }
+}
+
+#[test]
+fn day02_examples() {
+    let cases = [
+        (
+            "1,9,10,3,2,3,11,0,99,30,40,50",
+            "3500,9,10,70,2,3,11,0,99,30,40,50",
+        ),
+        ("1,0,0,0,99", "2,0,0,0,99"),
+        ("2,3,0,3,99", "2,3,0,6,99"),
+        ("2,4,4,5,99,0", "2,4,4,5,99,9801"),
+        ("1,1,1,4,99,5,6,0,99", "30,1,1,4,2,5,6,0,99"),
+    ];
+    for &(prog, mem) in &cases {
+        let r = run(prog, &[]);
+        assert_eq!(r.status, Status::Halted);
+        assert_eq!(r.cpu.mem.as_slice(), &parse_mem(mem)[..], "{}", prog);
+    }
+}
+
+#[test]
+fn day05_io_and_modes() {
+    assert_eq!(outputs("3,0,4,0,99", &[1234]), [1234]);
+
+    // Negative immediates and a write over the halt that follows
+    for &prog in &["1002,4,3,4,33", "1101,100,-1,4,0"] {
+        let r = run(prog, &[]);
+        assert_eq!(r.status, Status::Halted);
+        assert_eq!(r.cpu.mem.get(4), 99);
+    }
+}
+
+#[test]
+fn day05_comparisons() {
+    let equal_8 = ["3,9,8,9,10,9,4,9,99,-1,8", "3,3,1108,-1,8,3,4,3,99"];
+    let less_8 = ["3,9,7,9,10,9,4,9,99,-1,8", "3,3,1107,-1,8,3,4,3,99"];
+    for v in -2..12 {
+        for prog in &equal_8 {
+            assert_eq!(outputs(prog, &[v]), [(v == 8) as i64], "{} <- {}", prog, v);
+        }
+        for prog in &less_8 {
+            assert_eq!(outputs(prog, &[v]), [(v < 8) as i64], "{} <- {}", prog, v);
+        }
+    }
+}
+
+#[test]
+fn day05_jumps() {
+    let progs = [
+        "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9",
+        "3,3,1105,-1,9,1101,0,0,12,4,12,99,1",
+    ];
+    for prog in &progs {
+        assert_eq!(outputs(prog, &[0]), [0], "{}", prog);
+        assert_eq!(outputs(prog, &[-3]), [1], "{}", prog);
+        assert_eq!(outputs(prog, &[5]), [1], "{}", prog);
+    }
+
+    let prog = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,\
+                1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,\
+                999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
+    for v in 0..16 {
+        let expected = 1000 + (v > 8) as i64 - (v < 8) as i64;
+        assert_eq!(outputs(prog, &[v]), [expected], "{}", v);
+    }
+}
+
+#[test]
+fn day09_quine() {
+    let prog = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+    assert_eq!(outputs(prog, &[]), parse_mem(prog));
+}
+
+#[test]
+fn day09_large_numbers() {
+    let out = outputs("1102,34915192,34915192,7,4,7,99,0", &[]);
+    assert_eq!(out, [34_915_192 * 34_915_192]);
+    assert_eq!(out[0].to_string().len(), 16);
+
+    assert_eq!(
+        outputs("104,1125899906842624,99", &[]),
+        [1_125_899_906_842_624]
+    );
+}
+
+#[test]
+fn day09_relative_base() {
+    // The walkthrough: base 2000, `109,19` moves it to 2019 and `204,-34`
+    // reads 1985
+    let r = run("109,2000,109,19,21101,7,8,-34,204,-34,99", &[]);
+    assert_eq!(r.status, Status::Halted);
+    assert_eq!(r.outputs, [15]);
+    assert_eq!(r.cpu.rel, 2019);
+    assert_eq!(r.cpu.mem.get(1985), 15);
+
+    // Relative input
+    assert_eq!(outputs("109,10,203,0,204,0,99", &[-42]), [-42]);
+}
+
+#[test]
+fn negative_relative_offsets() {
+    assert_eq!(outputs("109,10,204,-5,99,77", &[]), [77]);
+    assert_eq!(outputs("109,7,109,-3,204,1,99,0", &[]), [1]);
+    assert_eq!(outputs("109,3,22101,5,-3,-1,4,2,99", &[]), [114]);
+}
+
+#[test]
+fn negative_addresses_fault() {
+    assert_eq!(fault("204,-1,99", &[]), (0, Fault::NegativeAddress(-1)));
+    assert_eq!(fault("4,-3,99", &[]), (0, Fault::NegativeAddress(-3)));
+    assert_eq!(
+        fault("104,1,109,-5,203,0,99", &[1]),
+        (4, Fault::NegativeAddress(-5))
+    );
+    // Jumping to a negative address
+    assert_eq!(fault("1105,1,-1", &[]), (0, Fault::NegativeAddress(-1)));
+}
+
+#[test]
+fn writes_past_end_of_program() {
+    let r = run("1101,2,3,100,4,100,99", &[]);
+    assert_eq!(r.outputs, [5]);
+    assert_eq!(r.cpu.mem.get(100), 5);
+    assert_eq!(r.cpu.mem.get(99), 0);
+
+    // Far enough out to land in sparse memory
+    let r = run("3,1000000000,4,1000000000,99", &[31]);
+    assert_eq!(r.status, Status::Halted);
+    assert_eq!(r.outputs, [31]);
+    assert_eq!(r.cpu.mem.get(1_000_000_000), 31);
+
+    // Reading past the end gives 0
+    assert_eq!(outputs("4,500,99", &[]), [0]);
+}
+
+#[test]
+fn immediate_writes_fault() {
+    assert_eq!(fault("11101,1,1,0,99", &[]), (0, Fault::WriteToImmediate));
+    assert_eq!(
+        fault("104,7,11108,1,1,0,99", &[]),
+        (2, Fault::WriteToImmediate)
+    );
+    assert_eq!(fault("103,0,99", &[5]), (0, Fault::WriteToImmediate));
+}
+
+#[test]
+fn bad_instructions_fault() {
+    assert_eq!(fault("98,0,0", &[]), (0, Fault::InvalidOpcode(98)));
+    assert_eq!(fault("1101,0,0,4,99", &[]), (4, Fault::InvalidOpcode(0)));
+    assert_eq!(fault("301,0,0,0,99", &[]), (0, Fault::InvalidMode(301)));
+    assert_eq!(
+        fault("1102,9223372036854775807,2,0,99", &[]),
+        (0, Fault::Overflow)
+    );
+}
+
+#[test]
+fn input_waits_without_losing_state() {
+    let mut cpu = Cpu::with_mem(parse_mem("104,1,3,9,4,9,99,0,0,0"));
+    let mut feed = None;
+    assert_eq!(cpu.run(&mut feed), Status::Output(1));
+    assert_eq!(cpu.run(&mut feed), Status::NeedsInput);
+    assert_eq!(cpu.pc, 2);
+
+    feed = Some(17);
+    assert_eq!(cpu.run(&mut feed), Status::Output(17));
+    assert_eq!(feed, None);
+    assert_eq!(cpu.run(&mut feed), Status::Halted);
+}
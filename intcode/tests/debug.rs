@@ -0,0 +1,147 @@
+use intcode::debug::{Debugger, Stop};
+use intcode::taint::{Labels, Sink};
+use intcode::{asm, parse_mem, Cpu};
+
+fn debugger(prog: &str, input: &[i64]) -> Debugger {
+    let mut dbg = Debugger::new(Cpu::with_mem(parse_mem(prog)));
+    dbg.input.extend(input);
+    dbg
+}
+
+#[test]
+fn step_back_undoes_taint() {
+    // IN [9]; ADD [9], [9], [10]; OUT [10]; HLT
+    let mut dbg = debugger("3,9,1,9,9,10,4,10,99,0,0", &[7]);
+    assert_eq!(dbg.command("tag in"), "Tagging input\n");
+    let tags = |dbg: &Debugger, addr| dbg.cpu.taint.as_ref().unwrap().labels(addr);
+    let flows = |dbg: &Debugger| dbg.cpu.taint.as_ref().unwrap().flows.clone();
+
+    assert_eq!(dbg.step_n(1), Stop::Stepped);
+    let after_in = flows(&dbg);
+    assert_eq!(after_in.len(), 1);
+
+    assert_eq!(dbg.step_n(2), Stop::Stepped);
+    assert_eq!(dbg.output, [14]);
+    assert_eq!(tags(&dbg, 10), Labels::from([0]));
+    let out = flows(&dbg).into_iter().filter(|f| f.to == Sink::Output(0));
+    assert_eq!(out.count(), 1);
+
+    assert_eq!(dbg.back_n(2), 2);
+    assert_eq!(tags(&dbg, 10), Labels::new());
+    assert_eq!(tags(&dbg, 9), Labels::from([0]));
+    assert_eq!(flows(&dbg), after_in);
+    assert_eq!(dbg.command("taint 10"), "[10] <- {}\n");
+
+    // Back past the write that tagged [9], and forward again
+    assert_eq!(dbg.back_to_write(9), Some(1));
+    assert_eq!(tags(&dbg, 9), Labels::new());
+    assert!(flows(&dbg).is_empty());
+    assert_eq!(dbg.cont(), Stop::Halted);
+    assert_eq!(flows(&dbg).len(), 3);
+    assert_eq!(tags(&dbg, 10), Labels::from([0]));
+}
+
+// Doubles each input until a 0, then writes past the end of the program
+const DOUBLER: &str = "
+        IN  [x]
+        ARB #x
+loop:   MUL rb+0, #2, rb+1
+        OUT rb+1
+        IN  rb+0
+        JIT rb+0, #loop
+        ADD [x], #1, [40]
+        OUT [40]
+        HLT
+x:      .data 0, 0
+";
+
+type State = (usize, i64, Vec<i64>, Vec<i64>, Vec<i64>);
+
+fn state(dbg: &Debugger) -> State {
+    (
+        dbg.cpu.pc,
+        dbg.cpu.rel,
+        (0..48).map(|a| dbg.cpu.mem.get(a)).collect(),
+        dbg.input.iter().copied().collect(),
+        dbg.output.clone(),
+    )
+}
+
+#[test]
+fn step_back_restores_everything() {
+    let mem = asm::assemble(DOUBLER).unwrap();
+    let mut dbg = Debugger::new(Cpu::with_mem(mem));
+    dbg.input.extend(&[3, 5, 0]);
+
+    // The state before each instruction
+    let mut states = vec![];
+    loop {
+        let before = state(&dbg);
+        match dbg.step() {
+            None => states.push(before),
+            Some(stop) => {
+                assert_eq!(stop, Stop::Halted);
+                break;
+            }
+        }
+    }
+    assert_eq!(states.len(), 12);
+    assert_eq!(dbg.output, [6, 10, 1]);
+    assert!(dbg.input.is_empty());
+    assert_eq!(dbg.cpu.mem.get(40), 1);
+
+    while let Some(before) = states.pop() {
+        assert!(dbg.step_back());
+        assert_eq!(state(&dbg), before);
+    }
+    assert!(!dbg.step_back());
+
+    // And runs the same way again
+    assert_eq!(dbg.cont(), Stop::Halted);
+    assert_eq!(dbg.output, [6, 10, 1]);
+}
+
+#[test]
+fn commands() {
+    let mem = asm::assemble(DOUBLER).unwrap();
+    let mut dbg = Debugger::new(Cpu::with_mem(mem));
+    // Without the listing at pc that follows a report
+    let mut run = |cmd: &str| -> Vec<String> {
+        let out = dbg.command(cmd);
+        let lines = out.lines().filter(|l| !l.starts_with("=>"));
+        lines.map(String::from).collect()
+    };
+
+    assert_eq!(run("b 8"), ["Breakpoint at 8"]);
+    assert_eq!(run("c"), ["Waiting for input"]);
+    assert!(run("in 3 5 0").is_empty());
+    assert_eq!(run("c"), ["Breakpoint at 8"]);
+    // An empty line repeats the `c`
+    assert_eq!(run(""), ["out: 6", "Breakpoint at 8"]);
+    assert_eq!(run("r"), ["pc=8 rel=22"]);
+    assert!(run("db 8").is_empty());
+    assert_eq!(run("db 8"), ["No breakpoint at 8"]);
+
+    assert_eq!(run("w 40"), ["Watching [40] = 0"]);
+    assert_eq!(run("c"), ["out: 10", "Watchpoint [40]: 0 -> 1"]);
+    assert_eq!(run("x 40 1"), ["   40:       1"]);
+    assert_eq!(run("rw 40"), ["Stepped back 1"]);
+    assert_eq!(run("x 40 1"), ["   40:       0"]);
+    assert_eq!(run("x 18446744073709551615 2"), ["Address out of range"]);
+    assert_eq!(
+        run("x 0 1000000000000"),
+        ["Count 1000000000000 is over 65536"]
+    );
+    assert_eq!(run("x 39 10").len(), 2);
+    assert_eq!(run("x 18446744073709551610 3").len(), 1);
+    assert_eq!(run("rw 40"), ["No write to [40] in the history"]);
+
+    // Editing memory drops the history
+    assert!(run("set 22 7").is_empty());
+    assert_eq!(run("rs"), ["Stepped back 0, no more history"]);
+    assert!(run("dw 40").is_empty());
+    assert_eq!(run("c"), ["out: 8", "Halted"]);
+    assert_eq!(run("s"), ["Halted"]);
+    assert_eq!(run("bogus"), ["Unknown command `bogus`, try help"]);
+    assert_eq!(run("s x"), ["Bad count `x`"]);
+}
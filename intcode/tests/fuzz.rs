@@ -0,0 +1,44 @@
+use intcode::format_mem;
+use intcode::fuzz::{check, Case, Rng, Source};
+
+// Seeds run on every `cargo test`, `intcode fuzz` goes further
+const SEEDS: u64 = 2000;
+
+#[test]
+fn rng_is_splitmix64() {
+    let mut rng = Rng::new(0);
+    assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+    assert_eq!(rng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+}
+
+#[test]
+fn cases_are_reproducible() {
+    for seed in 0..100 {
+        let a = Case::generate(&mut Rng::new(seed));
+        let b = Case::generate(&mut Rng::new(seed));
+        assert_eq!(a, b);
+    }
+}
+
+#[test]
+fn short_fuzzer_input_still_makes_a_case() {
+    let case = Case::from_bytes(&[]);
+    assert!(case.prog.len() >= 8);
+    check(&case).unwrap();
+}
+
+#[test]
+fn engines_agree() {
+    for seed in 0..SEEDS {
+        let case = Case::generate(&mut Rng::new(seed));
+        if let Err(d) = check(&case) {
+            panic!(
+                "seed {}: {}\nprogram {}\ninput {}",
+                seed,
+                d,
+                format_mem(&case.prog),
+                format_mem(&case.input)
+            );
+        }
+    }
+}
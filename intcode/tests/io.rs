@@ -0,0 +1,128 @@
+use std::sync::mpsc::{channel, sync_channel};
+use std::sync::{Arc, Mutex};
+
+use intcode::io::{Func, Iter};
+use intcode::{parse_mem, Cpu, Limit, Limits, Status};
+
+// IN [9]; OUT [9]; JIT #1, #0
+const ECHO: &str = "3,9,4,9,1105,1,0,99,0,0";
+
+// The day 7 part 2 example, phases 9,8,7,6,5 give 139629729
+const AMP: &str = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,\
+                   1005,28,6,99,0,0,5";
+
+#[test]
+fn amplifier_loop() {
+    let phases = [9, 8, 7, 6, 5];
+    let (txs, rxs): (Vec<_>, Vec<_>) = phases.iter().map(|_| channel()).unzip();
+    for (tx, &phase) in txs.iter().zip(&phases) {
+        tx.send(phase).unwrap();
+    }
+    txs[0].send(0).unwrap();
+
+    // The last amp feeds the first, and keeps a copy of what it said
+    let said = Arc::new(Mutex::new(vec![]));
+    let mut amps = vec![];
+    for (idx, rx) in rxs.into_iter().enumerate() {
+        let cpu = Cpu::with_mem(parse_mem(AMP));
+        let amp = match txs.get(idx + 1) {
+            Some(tx) => cpu.spawn(rx, tx.clone()),
+            None => {
+                let (tx, said) = (txs[0].clone(), said.clone());
+                let out = Func(move |v| {
+                    said.lock().unwrap().push(v);
+                    tx.send(v).ok();
+                });
+                cpu.spawn(rx, out)
+            }
+        };
+        amps.push(amp);
+    }
+    drop(txs);
+
+    for amp in amps {
+        assert_eq!(amp.join().unwrap().1, Status::Halted);
+    }
+    assert_eq!(said.lock().unwrap().last(), Some(&139629729));
+}
+
+#[test]
+fn hang_up_stops_waiting() {
+    let (in_tx, in_rx) = channel();
+    // Nothing buffered, each output waits for the test to take it
+    let (out_tx, out_rx) = sync_channel(0);
+    let echo = Cpu::with_mem(parse_mem(ECHO)).spawn(in_rx, out_tx);
+
+    for v in 1..=3 {
+        in_tx.send(v).unwrap();
+        assert_eq!(out_rx.recv(), Ok(v));
+    }
+    drop(in_tx);
+    let (cpu, status) = echo.join().unwrap();
+    assert_eq!(status, Status::NeedsInput);
+    assert_eq!(cpu.pc, 0);
+    assert!(out_rx.recv().is_err());
+
+    // Output nobody receives is dropped
+    let (out_tx, out_rx) = channel();
+    drop(out_rx);
+    let mut cpu = Cpu::with_mem(parse_mem(ECHO));
+    assert_eq!(cpu.run_io(Iter(1..=3), out_tx), Status::NeedsInput);
+}
+
+#[test]
+fn closures() {
+    let mut next = 0;
+    let input = Func(|| {
+        next += 1;
+        Some(next).filter(|&n| n <= 3)
+    });
+    let mut seen = vec![];
+    let mut cpu = Cpu::with_mem(parse_mem(ECHO));
+    assert_eq!(
+        cpu.run_io(input, Func(|v| seen.push(v * 10))),
+        Status::NeedsInput
+    );
+    assert_eq!(seen, [10, 20, 30]);
+    assert_eq!(next, 4);
+}
+
+#[test]
+fn run_io_keeps_input_across_a_limit() {
+    let mut cpu = Cpu::with_mem(parse_mem(ECHO));
+    cpu.set_limits(Limits {
+        instructions: Some(3),
+        ..Limits::default()
+    });
+    let mut input = Iter(vec![1, 2, 3].into_iter());
+    let mut out = vec![];
+    // The limit stops the second `IN` before it asks for 2, see the fault
+    // test for input that was read but not used
+    assert_eq!(
+        cpu.run_io(&mut input, &mut out),
+        Status::LimitExceeded {
+            pc: 0,
+            limit: Limit::Instructions
+        }
+    );
+    assert_eq!(out, [1]);
+
+    cpu.clear_limits();
+    assert_eq!(cpu.run_io(&mut input, &mut out), Status::NeedsInput);
+    assert_eq!(out, [1, 2, 3]);
+}
+
+#[test]
+fn run_io_keeps_input_across_a_fault() {
+    // ARB #-20; IN rb+0, which faults until rel is fixed up
+    let mut cpu = Cpu::with_mem(parse_mem("109,-20,203,0,4,30,99"));
+    let mut out = vec![];
+    assert!(matches!(
+        cpu.run_io(Iter(Some(5).into_iter()), &mut out),
+        Status::Fault { pc: 2, .. }
+    ));
+
+    cpu.rel = 30;
+    assert_eq!(cpu.run_io(Iter(None.into_iter()), &mut out), Status::Halted);
+    assert_eq!(out, [5]);
+}
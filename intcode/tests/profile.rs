@@ -0,0 +1,56 @@
+use intcode::{asm, Cpu, Status};
+
+// Calls a function three times, the frame it opens is named after pc 4
+const CALLS: &str = "
+        ADD #3, #0, [n]
+loop:   ARB #2
+        OUT [n]
+        ARB #-2
+        ADD [n], #-1, [n]
+        JIT [n], #loop
+        HLT
+n:      .data 0
+";
+
+#[test]
+fn counts_and_stacks() {
+    let mem = asm::assemble(CALLS).unwrap();
+    let mut cpu = Cpu::with_mem(mem.clone());
+    cpu.start_profile();
+    for v in &[3, 2, 1] {
+        assert_eq!(cpu.run(&mut None), Status::Output(*v));
+    }
+    assert_eq!(cpu.run(&mut None), Status::Halted);
+    let profile = cpu.take_profile();
+
+    assert_eq!(profile.total, 17);
+    assert_eq!(profile.per_pc[&0], 1);
+    assert_eq!(profile.per_pc[&4], 3);
+    assert_eq!(profile.per_pc[&17], 1);
+    let kinds = profile.per_kind();
+    assert_eq!((kinds["ADD"], kinds["ARB"], kinds["OUT"]), (4, 6, 3));
+    assert_eq!(kinds.values().sum::<u64>(), 17);
+
+    // The `ARB` that opens the frame counts outside it, the one that drops
+    // it inside
+    assert_eq!(profile.folded(), "main 11\nmain;@4 6\n");
+
+    let report = profile.report(&mem, 2);
+    let hot: Vec<&str> = report.lines().skip(3).take(2).collect();
+    assert!(report.starts_with("17 instructions\n\nHot spots:\n"));
+    assert!(hot[0].ends_with("4: ARB #2"), "{}", hot[0]);
+    assert!(hot[1].ends_with("6: OUT [18]"), "{}", hot[1]);
+    assert!(report.contains("By mode combination:\n"));
+}
+
+#[test]
+fn restarting_drops_counts() {
+    let mut cpu = Cpu::with_mem(asm::assemble(CALLS).unwrap());
+    cpu.start_profile();
+    assert_eq!(cpu.run(&mut None), Status::Output(3));
+    cpu.start_profile();
+    assert_eq!(cpu.run(&mut None), Status::Output(2));
+    // ARB #-2, ADD, JIT, ARB #2, OUT
+    assert_eq!(cpu.take_profile().total, 5);
+    assert_eq!(cpu.take_profile().total, 0);
+}
@@ -0,0 +1,88 @@
+use std::collections::VecDeque;
+
+use intcode::snapshot::{ErrorSnapshot, Snapshot};
+use intcode::{parse_mem, Cpu, Engine, Status};
+
+// ADD #2, #3, [100000]; IN [17]; OUT [17]; ARB #-100; IN rb+0; OUT rb+0;
+// HLT, the second `IN` faults until rel is fixed up
+const PROG: &str = "1101,2,3,100000,3,17,4,17,109,-100,203,0,204,0,99,0,0,0";
+
+// Run until the second `IN` has read its input and faulted
+fn stopped(engine: Engine) -> Snapshot {
+    let mut cpu = Cpu::with_engine(parse_mem(PROG), engine);
+    cpu.mem.set_limit(Some(200_000));
+    let mut input: VecDeque<i64> = vec![7, 8, 9].into();
+    let mut output = vec![];
+    assert!(matches!(
+        cpu.run_io(&mut input, &mut output),
+        Status::Fault { pc: 10, .. }
+    ));
+    Snapshot {
+        cpu,
+        input: input.into(),
+        output,
+    }
+}
+
+#[test]
+fn round_trip() {
+    for &engine in &[Engine::Interp, Engine::Cached] {
+        let snap = stopped(engine);
+        let text = snap.to_text();
+        assert_eq!(text.contains("engine cached"), engine == Engine::Cached);
+        assert!(text.contains("\nrel -100\npending 8\n"));
+        assert!(text.contains("\nlimit 200000\n"));
+        assert!(text.contains("\ninput 9\n"));
+        assert!(text.contains("\noutput 7\n"));
+        assert!(text.contains("\nsparse 100000=5\n"));
+
+        let back = Snapshot::parse(&text).unwrap();
+        assert_eq!(back.to_text(), text);
+        assert_eq!(back.cpu.engine(), engine);
+        assert_eq!(back.cpu.pc, 10);
+        assert_eq!(back.cpu.mem.limit(), Some(200_000));
+        assert_eq!(back.cpu.mem.get(100_000), 5);
+        assert_eq!(back.input, [9]);
+        assert_eq!(back.output, [7]);
+
+        // Picks up where it left off, the pending 8 going in first
+        let mut cpu = back.cpu;
+        cpu.rel = 17;
+        let mut input: VecDeque<i64> = back.input.into();
+        let mut output = back.output;
+        assert_eq!(cpu.run_io(&mut input, &mut output), Status::Halted);
+        assert_eq!(output, [7, 8]);
+        assert_eq!(input, [9]);
+    }
+}
+
+#[test]
+fn bad_snapshots() {
+    let version = |text: &str| matches!(Snapshot::parse(text), Err(ErrorSnapshot::Version(_)));
+    assert!(version(""));
+    assert!(version("intcode-snapshot 2\npc 0\nrel 0\nmem 99\n"));
+    assert!(version("intcode-snapshot 0\npc 0\nrel 0\nmem 99\n"));
+
+    let line = |text: &str| match Snapshot::parse(text) {
+        Err(ErrorSnapshot::Parse { line, .. }) => Some(line),
+        _ => None,
+    };
+    assert_eq!(
+        line("intcode-snapshot 1\nengine jit\npc 0\nrel 0\nmem 99\n"),
+        Some(2)
+    );
+    assert_eq!(
+        line("intcode-snapshot 1\npc 0\nrel 0\npending x\nmem 99\n"),
+        Some(4)
+    );
+    assert_eq!(line("intcode-snapshot 1\npc 0\nrel x\nmem 99\n"), Some(3));
+    assert_eq!(
+        line("intcode-snapshot 1\npc 0\nrel 0\ncolor red\nmem 99\n"),
+        Some(4)
+    );
+    assert_eq!(
+        line("intcode-snapshot 1\npc 0\nrel 0\nsparse 5\nmem 99\n"),
+        Some(4)
+    );
+    assert_eq!(line("intcode-snapshot 1\npc 0\nrel 0\n"), Some(0));
+}
@@ -0,0 +1,50 @@
+use intcode::symbolic::{End, Executor, Goal};
+use intcode::{parse_mem, Cpu, Status};
+
+const DAY02: &str = include_str!("../../day02/input");
+
+fn run(mut mem: Vec<i64>, noun: i64, verb: i64) -> i64 {
+    mem[1] = noun;
+    mem[2] = verb;
+    let mut cpu = Cpu::with_mem(mem);
+    assert_eq!(cpu.run(&mut None), Status::Halted);
+    cpu.mem[0]
+}
+
+#[test]
+fn day02_part2() {
+    let mem = parse_mem(DAY02);
+    let mut sym = Executor::new(&mem);
+    let noun = sym.symbol_at(1, 0..=99);
+    let verb = sym.symbol_at(2, 0..=99);
+
+    let v = sym.solve(Goal::Mem(0), 19690720).unwrap().unwrap();
+    assert_eq!((v[noun], v[verb]), (66, 35));
+    assert_eq!(run(mem.clone(), 66, 35), 19690720);
+
+    // Part 1's answer comes back out too, and one out of reach doesn't
+    let v = sym.solve(Goal::Mem(0), 4138687).unwrap().unwrap();
+    assert_eq!((v[noun], v[verb]), (12, 2));
+    assert_eq!(sym.solve(Goal::Mem(0), 1).unwrap(), None);
+}
+
+#[test]
+fn branches() {
+    // IN [11]; LT [11], #10, [12]; JIT [12], #10; HLT; HLT
+    let mut sym = Executor::new(&parse_mem("3,11,1007,11,10,12,1005,12,10,99,99,0,0"));
+    sym.symbolic_input(0..=20);
+
+    let paths = sym.explore().unwrap();
+    assert_eq!(paths.len(), 2);
+    assert!(paths.iter().all(|p| p.end == End::Halted && p.inputs == 1));
+
+    assert_eq!(
+        sym.solve(Goal::Mem(12), 1).unwrap().map(|v| v[0] < 10),
+        Some(true)
+    );
+    assert_eq!(
+        sym.solve(Goal::Mem(12), 0).unwrap().map(|v| v[0] >= 10),
+        Some(true)
+    );
+    assert_eq!(sym.solve(Goal::Mem(11), 21).unwrap(), None);
+}
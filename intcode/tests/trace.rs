@@ -0,0 +1,72 @@
+use intcode::trace::{self, ErrorTrace};
+use intcode::{parse_mem, Cpu, Step};
+
+fn recorded(prog: &str, input: &[i64]) -> Vec<trace::Event> {
+    let mut cpu = Cpu::with_mem(parse_mem(prog));
+    cpu.start_trace();
+    let mut input = input.iter().copied();
+    let mut feed = None;
+    while let Ok(step) = cpu.step(&mut feed) {
+        match step {
+            Step::Halted => break,
+            Step::NeedsInput => feed = input.next(),
+            _ => {}
+        }
+    }
+    cpu.trace.take().unwrap()
+}
+
+#[test]
+fn write_read_replay() {
+    let prog = "109,7,203,0,4,7,99,0";
+    let events = recorded(prog, &[42]);
+    assert_eq!(events.len(), 4);
+
+    let mut text = vec![];
+    trace::write(&mut text, &events).unwrap();
+    let read = trace::read(&String::from_utf8(text).unwrap()).unwrap();
+    assert_eq!(read, events);
+
+    let mut cpu = Cpu::with_mem(parse_mem(prog));
+    assert_eq!(trace::replay(&mut cpu, &read), Ok(4));
+
+    // Outputting another cell
+    let mut cpu = Cpu::with_mem(parse_mem("109,7,203,0,4,6,99,0"));
+    let d = trace::replay(&mut cpu, &read).unwrap_err();
+    assert_eq!(d.index, 2);
+    assert_eq!(d.expected.output, Some(42));
+    assert_eq!(d.actual.unwrap().output, Some(99));
+}
+
+#[test]
+fn malformed_lines() {
+    assert_eq!(trace::read("{é}").unwrap_err().line, 1);
+    assert_eq!(trace::read("\n{\"pc\":0}").unwrap_err().line, 2);
+    for line in &[
+        "[]",
+        "{",
+        "{\"",
+        "{\"pc\"}",
+        "{\"pc\":}",
+        "{\"pc\":é}",
+        "{\"é\":1}",
+        "{\"pc\":0,\"op\":\"é\",\"modes\":[],\"args\":[]}",
+        "{\"pc\":0,\"op\":\"HLT\",\"modes\":[],\"args\":[é]}",
+        "{\"pc\":0,\"op\":\"HLT\",\"modes\":[],\"args\":[}",
+        "{\"pc\":0,\"op\":\"HLT\" \"modes\":[],\"args\":[]}",
+    ] {
+        assert!(
+            matches!(trace::read(line), Err(ErrorTrace { line: 1, .. })),
+            "{}",
+            line
+        );
+    }
+
+    // Cut short, or with something multibyte in it, anywhere along the line
+    let line = recorded("203,3,99", &[-8])[0].to_json();
+    for (i, _) in line.char_indices().skip(1) {
+        assert!(trace::read(&line[..i]).is_err(), "{}", &line[..i]);
+        let mangled = format!("{}é{}", &line[..i], &line[i..]);
+        assert!(trace::read(&mangled).is_err(), "{}", mangled);
+    }
+}